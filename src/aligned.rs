@@ -18,7 +18,7 @@ where
 {
     type Item = u32;
     const LEN: usize = {
-        if B % mem::size_of::<u32>() != 0 {
+        if !B.is_multiple_of(mem::size_of::<u32>()) {
             panic!();
         }
 
@@ -28,15 +28,11 @@ where
     #[inline(always)]
     fn index(index: usize) -> Option<Self::Item> {
         let size = mem::size_of::<u32>();
-        let index = index * size;
+        let index = index.checked_mul(size)?;
 
         debug_assert!(D::as_data().as_ptr().cast::<u32>().is_aligned());
 
-        if B >= index + size {
-            let buf = D::as_data()[index..][..size].try_into().unwrap();
-            Some(u32::from_le_bytes(buf))
-        } else {
-            None
-        }
+        let buf = D::as_data().get(index..)?.get(..size)?;
+        Some(u32::from_le_bytes(buf.try_into().ok()?))
     }
 }
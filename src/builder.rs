@@ -6,9 +6,19 @@
 mod tests;
 mod build;
 mod codegen;
+mod range;
+mod dynamic;
+mod container;
+mod encode;
+mod cache;
 
 use std::{ cmp, fmt };
+use crate::phf::HashOne;
+use crate::stable::{ Stable, StableHash };
 pub use codegen::*;
+pub use range::*;
+pub use container::*;
+pub use cache::*;
 
 /// Static Map builder
 ///
@@ -18,12 +28,49 @@ pub struct MapBuilder<'a, K> {
     limit: Option<u64>,
     ord: Option<OrdFunc<'a, K>>,
     hash: Option<HashFunc<'a, K>>,
+    hash_sync: Option<(SyncHashFunc<'a, K>, HashKeysFunc<K>)>,
+    hash_wide: Option<HashWideFunc<'a, K>>,
+    hash_wide_sync: Option<(SyncHashWideFunc<'a, K>, HashWideKeysFunc<K>)>,
+    /// Wide hashes of keys computed once by `build` or `build_cached`
+    wide_hashes: Option<&'a [u128]>,
     next_seed: fn(u64, u64) -> u64,
     force_build: bool,
+    threads: usize,
+    fingerprint: Option<Fingerprint>,
+    params: PhfParams,
+    pilot_width: Option<PilotWidth>,
+    random_seed: bool,
+    normalize: Option<(NormalizeFunc<'a, K>, FindDuplicateFunc<K>)>,
 }
 
+/// Number of keys per shard of large map
+const SHARD_KEYS: usize = 1024 * 1024;
+
+/// Number of pilot seeds tried for each shard, before the seed of map is abandoned
+const SHARD_RETRIES: u32 = 16;
+
+const GOLDEN: u64 = 0x9e3779b97f4a7c15;
+
+/// Default seed progression, see [`MapBuilder::set_next_seed`].
+fn next_seed(init_seed: u64, c: u64) -> u64 {
+    let mut z = init_seed.wrapping_add(c.wrapping_add(1).wrapping_mul(GOLDEN));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Number of keys above which medium map uses u16 pilots by default
+const WIDE_PILOT_KEYS: usize = 4 * 1024 * 1024;
+
 pub type OrdFunc<'a, K> = &'a dyn Fn(&K, &K) -> cmp::Ordering;
 pub type HashFunc<'a, K> = &'a dyn Fn(u64, &K) -> u64;
+pub type SyncHashFunc<'a, K> = &'a (dyn Fn(u64, &K) -> u64 + Sync);
+pub type HashWideFunc<'a, K> = &'a dyn Fn(&K) -> u128;
+pub type SyncHashWideFunc<'a, K> = &'a (dyn Fn(&K) -> u128 + Sync);
+pub type NormalizeFunc<'a, K> = &'a dyn Fn(&K) -> K;
+type FindDuplicateFunc<K> = fn(&[K]) -> Option<(usize, usize)>;
+type HashKeysFunc<K> = fn(SyncHashFunc<'_, K>, usize, u64, &[K], &mut [u64]);
+type HashWideKeysFunc<K> = fn(SyncHashWideFunc<'_, K>, usize, &[K]) -> Box<[u128]>;
 
 impl<'a, K> Default for MapBuilder<'a, K> {
     fn default() -> Self {
@@ -32,35 +79,136 @@ impl<'a, K> Default for MapBuilder<'a, K> {
 }
 
 impl<'a, K> MapBuilder<'a, K> {
+    /// Builder with byte-identical output for identical inputs
+    ///
+    /// If no seed is set, the initial seed is `0x9e3779b97f4a7c15`,
+    /// use [`MapBuilder::set_random_seed`] to start from a random one.
     pub fn new() -> Self {
         MapBuilder {
             limit: None,
             seed: None,
             ord: None,
             hash: None,
+            hash_sync: None,
+            hash_wide: None,
+            hash_wide_sync: None,
+            wide_hashes: None,
             force_build: false,
-            next_seed: |init_seed, c| {
-                use std::hash::Hasher;
-
-                let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                hasher.write_u64(init_seed);
-                hasher.write_u64(c);
-                hasher.finish()
-            },
+            threads: 1,
+            fingerprint: None,
+            params: PhfParams::DEFAULT,
+            pilot_width: None,
+            next_seed,
+            random_seed: false,
+            normalize: None,
         }
     }
 
-    /// Try to construct even if keys is very large
+    /// Builder with byte-identical output for identical inputs
+    ///
+    /// This is the same as [`MapBuilder::new`], the initial seed is `0x9e3779b97f4a7c15`
+    /// unless it is set, and the seed progression is [`MapBuilder::set_next_seed`] default,
+    /// which does not depend on the Rust version.
+    ///
+    /// The output is also independent of [`MapBuilder::set_threads`],
+    /// so only the keys, hash and options determine it.
+    pub fn deterministic() -> Self {
+        MapBuilder::new()
+    }
+
+    /// Start from a random seed if no seed is set
+    ///
+    /// The output is different for each build, so it is not reproducible,
+    /// use [`MapOutput::seed`] with [`MapBuilder::set_seed`] to keep it.
+    pub fn set_random_seed(&mut self, flag: bool) -> &mut Self {
+        self.random_seed = flag;
+        self
+    }
+
+    /// Build a single unsharded medium map even if keys is very large
     pub fn force_build(&mut self, flag: bool) -> &mut Self {
         self.force_build = flag;
         self
     }
 
+    /// Number of threads used for construction
+    ///
+    /// This parallelizes the bucket partition, the pilot search of shards
+    /// and the remix of wide hashes.
+    ///
+    /// Keys are hashed in parallel only with [`MapBuilder::set_hash_sync`]
+    /// or [`MapBuilder::set_hash_wide_sync`], the hash of [`MapBuilder::set_hash`],
+    /// [`MapBuilder::set_stable_hash`] and [`MapBuilder::set_hash_wide`]
+    /// is always called from the calling thread.
+    ///
+    /// The output is the same as single thread for a given seed.
+    ///
+    /// `0` means to use the available parallelism.
+    pub fn set_threads(&mut self, n: usize) -> &mut Self {
+        self.threads = n;
+        self
+    }
+
+    fn threads(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            n => n
+        }
+    }
+
     pub fn set_limit(&mut self, limit: Option<u64>) -> &mut Self {
         self.limit = limit;
         self
     }
 
+    /// Store a fingerprint of hash for each key
+    ///
+    /// Small, medium and large maps check the fingerprint before loading the key,
+    /// so that most misses don't need to access the key store.
+    pub fn set_fingerprint(&mut self, fingerprint: Option<Fingerprint>) -> &mut Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// Parameters of medium and large map
+    ///
+    /// See [`PhfParams`] for the presets.
+    pub fn set_params(&mut self, params: PhfParams) -> &mut Self {
+        self.params = params;
+        self
+    }
+
+    /// Width of pilots of medium and large map
+    ///
+    /// With u16 pilots, 65536 pilots are tried for each bucket instead of 256,
+    /// so a seed is rarely abandoned, but the pilot table is twice as large.
+    ///
+    /// `None` means to use u16 pilots for a medium map of more than 4M keys.
+    pub fn set_pilot_width(&mut self, width: Option<PilotWidth>) -> &mut Self {
+        self.pilot_width = width;
+        self
+    }
+
+    fn init_seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| if self.random_seed {
+            use std::hash::BuildHasher;
+
+            std::collections::hash_map::RandomState::new().hash_one(0x42)
+        } else {
+            GOLDEN
+        })
+    }
+
+    fn pilot_width(&self, keys_len: usize) -> PilotWidth {
+        self.pilot_width.unwrap_or(if keys_len > WIDE_PILOT_KEYS {
+            PilotWidth::U16
+        } else {
+            PilotWidth::U8
+        })
+    }
+
     pub fn set_seed(&mut self, seed: u64) -> &mut Self {
         self.seed = Some(seed);
         self
@@ -73,9 +221,98 @@ impl<'a, K> MapBuilder<'a, K> {
 
     pub fn set_hash(&mut self, f: HashFunc<'a, K>) -> &mut Self {
         self.hash = Some(f);
+        self.hash_sync = None;
+        self
+    }
+
+    /// Same as [`MapBuilder::set_hash`], but keys are hashed in parallel
+    /// when [`MapBuilder::set_threads`] is more than one.
+    pub fn set_hash_sync(&mut self, f: SyncHashFunc<'a, K>) -> &mut Self
+    where
+        K: Sync
+    {
+        self.hash = Some(f);
+        self.hash_sync = Some((f, build::hash_keys_sync::<K>));
         self
     }
 
+    /// Hash keys by [`StableHash`](crate::stable::StableHash)
+    ///
+    /// Same as the runtime hash [`Stable<H>`](crate::stable::Stable),
+    /// so the hash is the same on build host and target.
+    pub fn set_stable_hash<H>(&mut self) -> &mut Self
+    where
+        H: HashOne + 'a,
+        K: StableHash,
+    {
+        self.hash = Some(&Stable::<H>::hash_one::<K>);
+        self.hash_sync = None;
+        self
+    }
+
+    /// Hash each key only once to a wide hash
+    ///
+    /// The hash of each seed is derived by [`remix`](crate::phf::remix),
+    /// this is faster when keys are expensive to hash.
+    /// Note that the runtime hash must be [`Remix`](crate::phf::Remix) of same wide hash.
+    ///
+    /// This takes precedence over [`MapBuilder::set_hash`].
+    pub fn set_hash_wide(&mut self, f: HashWideFunc<'a, K>) -> &mut Self {
+        self.hash_wide = Some(f);
+        self.hash_wide_sync = None;
+        self
+    }
+
+    /// Same as [`MapBuilder::set_hash_wide`], but keys are hashed in parallel
+    /// when [`MapBuilder::set_threads`] is more than one.
+    pub fn set_hash_wide_sync(&mut self, f: SyncHashWideFunc<'a, K>) -> &mut Self
+    where
+        K: Sync
+    {
+        self.hash_wide = Some(f);
+        self.hash_wide_sync = Some((f, build::hash_wide_sync::<K>));
+        self
+    }
+
+    /// Normalize keys before build
+    ///
+    /// The map is built from the normalized keys, and the build fails
+    /// if two keys are equal after normalization.
+    /// The hash and ord methods take normalized keys,
+    /// and the keys stored in map should be normalized too.
+    ///
+    /// The normalized keys are not kept, and the output index refers to the original keys,
+    /// so store them with `output.reorder(&keys).map(f)`.
+    /// The normalized key is returned by value,
+    /// so `K` should be an owned type such as `String`, not `&str`.
+    ///
+    /// For example, `k.to_ascii_lowercase()` with the hash of
+    /// [`AsciiCaseInsensitive`](crate::equivalent::AsciiCaseInsensitive)
+    /// builds a map queried ignoring ASCII case.
+    pub fn set_normalize(&mut self, f: NormalizeFunc<'a, K>) -> &mut Self
+    where
+        K: Eq + std::hash::Hash
+    {
+        self.normalize = Some((f, build::find_duplicate::<K>));
+        self
+    }
+
+    fn normalize(&self, keys: &[K]) -> Result<Option<Vec<K>>, BuildFailed> {
+        let Some((f, find_duplicate)) = self.normalize else {
+            return Ok(None);
+        };
+
+        let keys = keys.iter().map(f).collect::<Vec<_>>();
+        match find_duplicate(&keys) {
+            Some((first, second)) => Err(BuildFailed::DuplicateKey { first, second }),
+            None => Ok(Some(keys))
+        }
+    }
+
+    /// Derive the seed of `c`th retry from initial seed
+    ///
+    /// The default is the `c + 1`th output of SplitMix64 seeded with the initial seed,
+    /// that is `splitmix64(init_seed + (c + 1) * 0x9e3779b97f4a7c15)` with wrapping arithmetic.
     pub fn set_next_seed(&mut self, f: fn(u64, u64) -> u64)
         -> &mut Self
     {
@@ -87,13 +324,32 @@ impl<'a, K> MapBuilder<'a, K> {
     ///
     /// # NOTE
     ///
-    /// Note that the keys used must be unique, otherwise the build will not succeed.
+    /// Note that the keys used must be unique,
+    /// the duplicate keys are reported as [`BuildFailed::DuplicateKey`]
+    /// or [`BuildFailed::HashCollision`] before searching seeds.
     pub fn build(&self, keys: &[K]) -> Result<MapOutput, BuildFailed> {
+        if let Some(keys) = self.normalize(keys)? {
+            return MapBuilder { normalize: None, ..*self }.build(&keys);
+        }
+
+        if u32::try_from(keys.len()).is_err() {
+            return Err(BuildFailed::TooManyKeys { len: keys.len() });
+        }
+
+        let wide = match self.wide_hashes {
+            Some(_) => None,
+            None => build::hash_wide(self, keys)
+        };
+        if let Some(wide) = wide {
+            // Each key is hashed only once, even if small map falls back to medium map.
+            return MapBuilder { wide_hashes: Some(&wide), ..*self }.build(keys);
+        }
+
         if keys.len() <= 16 {
             // For tiny amounts of data, binary search is usually faster.
             //
             // At most 4 comparisons will be faster than a high-quality hash.
-            if let Some(output) = build::build_tiny(self, keys) {
+            if let Some(output) = build::build_tiny(self, keys)? {
                 return Ok(output);
             }
         }
@@ -105,16 +361,16 @@ impl<'a, K> MapBuilder<'a, K> {
             // but for large numbers of keys, this may not be able to find the seed in a reasonable time.
             //
             // If the keys length is greater than 12, it will usually fallback to medium map.
-            if let Some(output) = build::build_small(self, keys) {
+            if let Some(output) = build::build_small(self, keys)? {
                 return Ok(output);
             }
         }
 
         if !self.force_build && keys.len() > 10 * 1024 * 1024 {
-            return Err(BuildFailed("WARN: \
-                We currently don't have good support for large numbers of keys,\
-                and this construction may be slow or not complete in a reasonable time.\
-            "));
+            // Split the keys into shards by hash, and build each shard as a medium map.
+            //
+            // This keeps the search buffers bounded by the shard size and threads, see `build_large`.
+            return build::build_large(self, keys, SHARD_KEYS);
         }
 
         // A typical PHF, but not optimized for construction time, and no sharding.
@@ -123,25 +379,178 @@ impl<'a, K> MapBuilder<'a, K> {
         // but for data larger than that it is better to use a specialized PHF library.
         build::build_medium(self, keys)
     }
+
+    /// Creates a Map with the seed cached by name
+    ///
+    /// The cached seed is tried first if the key set is unchanged,
+    /// it takes precedence over [`MapBuilder::set_seed`].
+    /// The seed that succeeded is written back to cache,
+    /// call [`SeedCache::save`] after all maps are built.
+    ///
+    /// The key set fingerprint is computed by the hash with seed `0`,
+    /// so it is independent of the order of keys.
+    /// The params, pilot width, fingerprint and force build options are mixed into it,
+    /// a keyless store is chosen when generating code, so it does not need to be.
+    /// If there is no hash method, this is the same as [`MapBuilder::build`].
+    pub fn build_cached(&self, name: &str, keys: &[K], cache: &mut SeedCache)
+        -> Result<MapOutput, BuildFailed>
+    {
+        if let Some(keys) = self.normalize(keys)? {
+            return MapBuilder { normalize: None, ..*self }.build_cached(name, &keys, cache);
+        }
+
+        let wide = match self.wide_hashes {
+            Some(_) => None,
+            None => build::hash_wide(self, keys)
+        };
+        if let Some(wide) = wide {
+            // The wide hashes of fingerprint are reused by build.
+            return MapBuilder { wide_hashes: Some(&wide), ..*self }.build_cached(name, keys, cache);
+        }
+
+        let Some(fingerprint) = build::key_fingerprint(self, keys) else {
+            return self.build(keys);
+        };
+
+        let output = match cache.get(name, fingerprint) {
+            Some(seed) => MapBuilder { seed: Some(seed), ..*self }.build(keys)?,
+            None => self.build(keys)?
+        };
+
+        if let Some(seed) = output.seed() {
+            cache.insert(name, fingerprint, seed);
+        }
+
+        Ok(output)
+    }
+
+    /// Creates an ordered Map with the specified keys
+    ///
+    /// Unlike [`MapBuilder::build`], this always builds a [`OrderedMap`](crate::OrderedMap),
+    /// which supports ordered query at any size.
+    pub fn build_ordered(&self, keys: &[K]) -> Result<MapOutput, BuildFailed> {
+        if let Some(keys) = self.normalize(keys)? {
+            return MapBuilder { normalize: None, ..*self }.build_ordered(&keys);
+        }
+
+        build::build_ordered(self, keys)
+    }
 }
 
-#[derive(Debug)]
-pub struct BuildFailed(&'static str);
+/// Fingerprint width
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fingerprint {
+    U8,
+    U16,
+}
+
+/// Pilot width
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PilotWidth {
+    U8,
+    U16,
+}
+
+/// Parameters of the PtrHash-style PHF used by medium and large map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhfParams {
+    /// Load factor, keys per slot, in `(0, 1]`.
+    ///
+    /// The slots beyond the number of keys are mapped back by a remap table,
+    /// `1.0` means there is no remap table,
+    /// but it is harder to find pilots.
+    pub alpha: f64,
+    /// Average keys per bucket, there is one pilot byte per bucket.
+    ///
+    /// The larger it is, the smaller the pilot table, but the slower the build.
+    pub lambda: f64,
+}
+
+impl PhfParams {
+    pub const DEFAULT: PhfParams = PhfParams { alpha: 0.99, lambda: 3.0 };
+
+    /// More buckets, so pilots are easy to find.
+    pub const fn fastest_build() -> PhfParams {
+        PhfParams { alpha: 0.98, lambda: 2.5 }
+    }
+
+    /// Fewer buckets, so the pilot table is smaller.
+    pub const fn smallest() -> PhfParams {
+        PhfParams { alpha: 0.99, lambda: 3.4 }
+    }
+
+    /// No remap table, so every lookup takes the same path.
+    pub const fn fastest_query() -> PhfParams {
+        PhfParams { alpha: 1.0, lambda: 2.5 }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.alpha > 0.0 && self.alpha <= 1.0 && self.lambda >= 1.0
+    }
+}
+
+impl Default for PhfParams {
+    fn default() -> Self {
+        PhfParams::DEFAULT
+    }
+}
+
+/// Build error
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildFailed {
+    /// Two keys are equal, by ord method or after normalization.
+    DuplicateKey { first: usize, second: usize },
+    /// Two keys have the same hash for every seed,
+    /// they are probably equal but there is no ord method to tell.
+    HashCollision { first: usize, second: usize },
+    /// No seed was found within [`MapBuilder::set_limit`].
+    LimitExhausted { limit: u64 },
+    MissingHash,
+    MissingOrd,
+    /// The index of key must fit in `u32`.
+    TooManyKeys { len: usize },
+    InvalidParams,
+    EmptyRange,
+    OverlappingRanges,
+}
 
 #[derive(Debug)]
 enum MapKind {
     Tiny,
+    Ordered,
     Small(u64),
     Medium {
         seed: u64,
-        pilots: Box<[u8]>,
+        pilots: Pilots,
+        remap: Box<[u32]>,
+    },
+    Large {
+        seed: u64,
+        key_offsets: Box<[u32]>,
+        slot_offsets: Box<[u32]>,
+        shard_retries: Box<[u32]>,
+        pilots: Pilots,
         remap: Box<[u32]>,
     }
 }
 
 impl fmt::Display for BuildFailed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(self.0)
+        match self {
+            BuildFailed::DuplicateKey { first, second } =>
+                write!(f, "duplicate keys at index {} and {}", first, second),
+            BuildFailed::HashCollision { first, second } =>
+                write!(f, "keys at index {} and {} have the same hash for every seed", first, second),
+            BuildFailed::LimitExhausted { limit } =>
+                write!(f, "no seed found within limit {}", limit),
+            BuildFailed::MissingHash => f.write_str("need hash method"),
+            BuildFailed::MissingOrd => f.write_str("need ord method"),
+            BuildFailed::TooManyKeys { len } => write!(f, "too many keys: {}", len),
+            BuildFailed::InvalidParams => f.write_str("invalid params"),
+            BuildFailed::EmptyRange => f.write_str("empty range"),
+            BuildFailed::OverlappingRanges => f.write_str("overlapping ranges"),
+        }
     }
 }
 
@@ -151,5 +560,18 @@ impl std::error::Error for BuildFailed {}
 #[derive(Debug)]
 pub struct MapOutput {
     kind: MapKind,
-    index: Box<[usize]>
+    index: Box<[usize]>,
+    fingerprints: Option<Fingerprints>,
+}
+
+#[derive(Debug)]
+enum Pilots {
+    U8(Box<[u8]>),
+    U16(Box<[u16]>),
+}
+
+#[derive(Debug)]
+enum Fingerprints {
+    U8(Box<[u8]>),
+    U16(Box<[u16]>),
 }
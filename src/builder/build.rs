@@ -1,42 +1,84 @@
-use std::cmp;
-use crate::{ phf, fast_reduct32, low, high };
+use std::{ cmp, thread };
+use std::borrow::Cow;
+use crate::{ phf, eytzinger, fast_reduct32, shard_index, shard_seed, low, high };
+use crate::store::Fingerprint;
 use super::*;
 
 pub(super) fn build_tiny<K>(builder: &MapBuilder<'_, K>, keys: &[K])
-    -> Option<MapOutput>
+    -> Result<Option<MapOutput>, BuildFailed>
 {
-    let ord = builder.ord.as_ref()?;
+    let Some(ord) = builder.ord else {
+        return Ok(None);
+    };
 
     let mut index = (0..keys.len()).collect::<Box<[_]>>();
     index.sort_by(|&x, &y| ord(&keys[x], &keys[y]));
+    check_sorted(ord, keys, &index)?;
 
-    Some(MapOutput {
+    Ok(Some(MapOutput {
         kind: MapKind::Tiny,
-        index
-    }) 
+        index,
+        fingerprints: None
+    }))
+}
+
+pub(super) fn build_ordered<K>(builder: &MapBuilder<'_, K>, keys: &[K])
+    -> Result<MapOutput, BuildFailed>
+{
+    let ord = builder.ord.ok_or(BuildFailed::MissingOrd)?;
+
+    let mut sorted = (0..keys.len()).collect::<Box<[_]>>();
+    sorted.sort_by(|&x, &y| ord(&keys[x], &keys[y]));
+    check_sorted(ord, keys, &sorted)?;
+
+    // in-order traversal of eytzinger layout
+    let mut index = vec![0; keys.len()].into_boxed_slice();
+    let mut k = eytzinger::first(keys.len());
+    for &idx in sorted.iter() {
+        index[k - 1] = idx;
+        k = eytzinger::next(k, keys.len());
+    }
+
+    Ok(MapOutput {
+        kind: MapKind::Ordered,
+        index,
+        fingerprints: None
+    })
+}
+
+/// Equal keys are adjacent after sorting
+fn check_sorted<K>(ord: OrdFunc<'_, K>, keys: &[K], sorted: &[usize]) -> Result<(), BuildFailed> {
+    match sorted.windows(2).find(|pair| ord(&keys[pair[0]], &keys[pair[1]]).is_eq()) {
+        Some(pair) => Err(BuildFailed::DuplicateKey {
+            first: pair[0].min(pair[1]),
+            second: pair[0].max(pair[1])
+        }),
+        None => Ok(())
+    }
 }
 
 pub(super) fn build_small<K>(builder: &MapBuilder<'_, K>, keys: &[K])
-    -> Option<MapOutput>
+    -> Result<Option<MapOutput>, BuildFailed>
 {
-    let hash = builder.hash.as_ref()?;
+    let Some(hashing) = Hashing::new(builder, keys) else {
+        return Ok(None);
+    };
     let next_seed = builder.next_seed;
     
-    let init_seed = builder.seed.unwrap_or_else(|| {
-        use std::hash::BuildHasher;
-    
-        std::collections::hash_map::RandomState::new().hash_one(0x42)
-    });
+    let init_seed = builder.init_seed();
     let mut seed = init_seed;
 
-    let mut hashes = Vec::with_capacity(keys.len());
+    let mut hashes = vec![0; keys.len()].into_boxed_slice();
     let mut map = vec![None; keys.len()];
     let keys_len: u32 = keys.len().try_into().unwrap();
 
     'search: for c in 0..(128 * 1024) {
         map.iter_mut().for_each(|idx| *idx = None);
-        hashes.clear();
-        hashes.extend(keys.iter().map(|v| hash(seed, v)));
+        hashing.hash_keys(1, seed, keys, &mut hashes);
+
+        if c == 0 {
+            hashing.check_hashes(builder, seed, keys, &hashes)?;
+        }
 
         for (idx, &v) in hashes.iter().enumerate() {
             let new_idx = fast_reduct32(high(v) ^ low(v), keys_len) as usize;
@@ -50,12 +92,15 @@ pub(super) fn build_small<K>(builder: &MapBuilder<'_, K>, keys: &[K])
         break
     }
 
-    let map = map.into_iter().collect::<Option<Box<[usize]>>>()?;
+    let Some(map) = map.into_iter().collect::<Option<Box<[usize]>>>() else {
+        return Ok(None);
+    };
 
-    Some(MapOutput {
+    Ok(Some(MapOutput {
         kind: MapKind::Small(seed),
+        fingerprints: fingerprints(builder, &map, &hashes),
         index: map
-    })
+    }))
 }
 
 pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
@@ -68,66 +113,88 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
     // we are currently much slower than the official implementation.
     // but it's basically fast enough for the scale of embedded binaries that are suitable.
     
-    #[derive(Default)]
-    struct Bucket {
-        slots: Vec<usize>
-    }
-
-    struct Slot {
-        bucket: u32,
-        keys_idx: usize,
+    if !builder.params.is_valid() {
+        return Err(BuildFailed::InvalidParams);
     }
 
-    fn reduct(hashes: &[u64], idx: usize, hp: u64, slots_len: u32) -> u32 {
-        fast_reduct32(high(hashes[idx]) ^ high(hp) ^ low(hp), slots_len)
-    }
-    
-    let hash = builder.hash.as_ref().ok_or(BuildFailed("need hash method"))?;
+    let hashing = Hashing::new(builder, keys).ok_or(BuildFailed::MissingHash)?;
     let next_seed = builder.next_seed;
     
-    let init_seed = builder.seed.unwrap_or_else(|| {
-        use std::hash::BuildHasher;
-    
-        std::collections::hash_map::RandomState::new().hash_one(0x42)
-    });
+    let init_seed = builder.init_seed();
     let mut seed = init_seed;
-
-    let alpha = 0.99;
-    let lambda = 3.0;
+    let threads = builder.threads();
 
     let keys_len: u32 = keys.len().try_into().unwrap();
-    let slots_len = {
-        let len = (f64::from(keys_len) / alpha).ceil() as u32;
+    let slots_len = slots_len(&builder.params, keys_len);
+    let width = builder.pilot_width(keys.len());
+    let mut search = PilotSearch::new(buckets_len(&builder.params, keys_len), width);
+    let mut hashes = vec![0; keys.len()].into_boxed_slice();
 
-        // Avoid powers of two, since then %S does not depend on all bits.
-        len + (len.is_power_of_two() as u32)
-    };
-    let buckets_len = {
-        let len = (f64::from(keys_len) / lambda).ceil() as u32;
+    for c in 0.. {
+        if builder.limit
+            .filter(|&limit| c > limit)
+            .is_some()
+        {
+            break
+        }
 
-        // Add a few extra buckets to avoid collisions for small n.
-        len + 3
-    };
+        hashing.hash_keys(threads, seed, keys, &mut hashes);
 
-    let mut buckets = (0..buckets_len)
-        .map(|_| Bucket::default())
-        .collect::<Box<[_]>>();
-    let mut pilots = vec![0; buckets_len as usize].into_boxed_slice();
-    let mut order = (0..buckets_len).collect::<Box<_>>();
-    let mut slots = (0..slots_len).map(|_| None).collect::<Box<[_]>>();
-    let mut hashes = vec![0; keys.len()].into_boxed_slice();
-    let mut stack = Vec::new();
+        if c == 0 {
+            hashing.check_hashes(builder, seed, keys, &hashes)?;
+        }
 
-    // since the number is small enough, we just use naive search
-    let mut values_to_add = Vec::with_capacity(lambda as usize * 2);
-    let mut recent = Vec::new();
-    let mut already_scored = Vec::new();
+        if !search.search(threads, seed, &hashes, slots_len) {
+            // No available pilot was found, so this seed is abandoned.
+            seed = next_seed(init_seed, c);
+            continue
+        }
 
-    'search: for c in 0.. {
-        buckets.iter_mut().for_each(|bucket| bucket.slots.clear());
-        pilots.iter_mut().for_each(|p| *p = 0);
-        slots.iter_mut().for_each(|slot| *slot = None);
+        let (index, remap) = search.remap(keys.len());
+
+        return Ok(MapOutput {
+            kind: MapKind::Medium {
+                seed,
+                pilots: Pilots::new(width, search.pilots),
+                remap
+            },
+            fingerprints: fingerprints(builder, &index, &hashes),
+            index
+        });
+    }
+
+    Err(BuildFailed::LimitExhausted { limit: builder.limit.unwrap_or(u64::MAX) })
+}
 
+pub(super) fn build_large<K>(builder: &MapBuilder<'_, K>, keys: &[K], shard_keys: usize)
+    -> Result<MapOutput, BuildFailed>
+{
+    // Same as medium map, but the keys are split into shards by hash,
+    // and each shard has its own pilots and remap.
+    //
+    // Besides the keys and the output, the memory is bounded by
+    // the hash (8 bytes), the shard order (4 bytes) and the wide hash (16 bytes, if any) of each key,
+    // and the search buffers of one shard per thread.
+
+    if !builder.params.is_valid() {
+        return Err(BuildFailed::InvalidParams);
+    }
+
+    let hashing = Hashing::new(builder, keys).ok_or(BuildFailed::MissingHash)?;
+    let next_seed = builder.next_seed;
+
+    let init_seed = builder.init_seed();
+    let mut seed = init_seed;
+    let threads = builder.threads();
+
+    let params = builder.params;
+    let width = builder.pilot_width(shard_keys);
+    let shards_len: u32 = keys.len().div_ceil(shard_keys).max(1).try_into().unwrap();
+    let mut hashes = vec![0; keys.len()].into_boxed_slice();
+    let mut order = vec![0; keys.len()].into_boxed_slice();
+    let mut key_offsets = vec![0; shards_len as usize + 1].into_boxed_slice();
+
+    for c in 0.. {
         if builder.limit
             .filter(|&limit| c > limit)
             .is_some()
@@ -135,21 +202,458 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
             break
         }
 
-        hashes.iter_mut()
-            .enumerate()
-            .for_each(|(idx, v)| {
-                *v = hash(seed, &keys[idx]);
-            });
+        hashing.hash_keys(threads, seed, keys, &mut hashes);
+
+        if c == 0 {
+            hashing.check_hashes(builder, seed, keys, &hashes)?;
+        }
 
+        // counting sort keys by shard
+        key_offsets.iter_mut().for_each(|n| *n = 0);
+        for &v in hashes.iter() {
+            key_offsets[shard_index(v, shards_len) as usize + 1] += 1;
+        }
+        for i in 1..key_offsets.len() {
+            key_offsets[i] += key_offsets[i - 1];
+        }
+        let mut cursor = key_offsets[..shards_len as usize].to_vec();
         for (idx, &v) in hashes.iter().enumerate() {
-            let bucket_idx = fast_reduct32(low(v), buckets_len) as usize;
-            buckets[bucket_idx].slots.push(idx);
+            let shard = shard_index(v, shards_len) as usize;
+            order[cursor[shard] as usize] = idx.try_into().unwrap();
+            cursor[shard] += 1;
+        }
+
+        // The buckets of each shard are sized by the average shard length,
+        // so the pilots do not depend on the largest shard.
+        let buckets_len = buckets_len(&params, keys.len().div_ceil(shards_len as usize).try_into().unwrap());
+
+        let mut index = vec![0; keys.len()].into_boxed_slice();
+        let mut slot_offsets = Vec::with_capacity(key_offsets.len());
+        let mut pilots = Vec::with_capacity(shards_len as usize * buckets_len as usize);
+        let mut remap = Vec::new();
+        let mut shard_retries = Vec::with_capacity(shards_len as usize);
+        slot_offsets.push(0);
+
+        let mut searches = (0..threads.min(shards_len as usize))
+            .map(|_| (PilotSearch::new(buckets_len, width), Vec::new()))
+            .collect::<Vec<_>>();
+        let mut failed = false;
+
+        // Shards are searched in batches of one shard per thread,
+        // and each batch is appended to the output in order before the next one,
+        // so at most one shard per thread is kept besides the output.
+        for batch_start in (0..shards_len as usize).step_by(searches.len()) {
+            let batch = batch_start..cmp::min(batch_start + searches.len(), shards_len as usize);
+            let retries = thread::scope(|s| {
+                let handles = searches.iter_mut()
+                    .zip(batch.clone())
+                    .map(|((search, shard_hashes), shard)| {
+                        let order = &order;
+                        let hashes = &hashes;
+                        let key_offsets = &key_offsets;
+
+                        s.spawn(move || {
+                            let shard_order = &order[key_offsets[shard] as usize..key_offsets[shard + 1] as usize];
+                            let slots_len = slots_len(&params, key_offsets[shard + 1] - key_offsets[shard]);
+
+                            shard_hashes.clear();
+                            shard_hashes.extend(shard_order.iter().map(|&idx| hashes[idx as usize]));
+
+                            // Retry only this shard with another pilot seed,
+                            // the shard of keys depends on the seed of map, so it stays the same.
+                            //
+                            // Shards are already searched in parallel, so each shard is partitioned in this thread.
+                            (0..SHARD_RETRIES)
+                                .find(|&retry| search.search(1, shard_seed(seed, retry), shard_hashes, slots_len))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                handles.into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect::<Option<Vec<_>>>()
+            });
+
+            let Some(retries) = retries else {
+                failed = true;
+                break
+            };
+
+            for (((search, _), shard), retry) in searches.iter().zip(batch).zip(retries) {
+                let (shard_start, shard_end) = (key_offsets[shard], key_offsets[shard + 1]);
+                let shard_order = &order[shard_start as usize..shard_end as usize];
+                let (shard_pos, shard_remap) = search.remap(shard_order.len());
+
+                for (pos, local_idx) in shard_pos.iter().enumerate() {
+                    index[shard_start as usize + pos] = shard_order[*local_idx] as usize;
+                }
+                remap.extend(shard_remap.iter().map(|&pos| shard_start + pos));
+                pilots.extend_from_slice(&search.pilots);
+                let slots_len: u32 = search.slots.len().try_into().unwrap();
+                slot_offsets.push(slot_offsets.last().unwrap() + slots_len);
+                shard_retries.push(retry);
+            }
+        }
+
+        if failed {
+            // No available pilot was found with any pilot seed of a shard,
+            // so this seed is abandoned.
+            seed = next_seed(init_seed, c);
+            continue
+        }
+
+        return Ok(MapOutput {
+            kind: MapKind::Large {
+                seed,
+                key_offsets,
+                slot_offsets: slot_offsets.into_boxed_slice(),
+                shard_retries: shard_retries.into_boxed_slice(),
+                pilots: Pilots::new(width, pilots.into_boxed_slice()),
+                remap: remap.into_boxed_slice()
+            },
+            fingerprints: fingerprints(builder, &index, &hashes),
+            index
+        });
+    }
+
+    Err(BuildFailed::LimitExhausted { limit: builder.limit.unwrap_or(u64::MAX) })
+}
+
+impl Pilots {
+    fn new(width: PilotWidth, pilots: Box<[u16]>) -> Pilots {
+        match width {
+            PilotWidth::U8 => Pilots::U8(pilots.iter().map(|&p| p as u8).collect()),
+            PilotWidth::U16 => Pilots::U16(pilots)
         }
+    }
+}
 
-        order.sort_unstable_by_key(|&bucket_idx| cmp::Reverse(buckets[bucket_idx as usize].slots.len()));
+/// Order independent fingerprint of key set and the options that change the output, for seed cache
+pub(super) fn key_fingerprint<K>(builder: &MapBuilder<'_, K>, keys: &[K]) -> Option<u64> {
+    let hashing = Hashing::new(builder, keys)?;
+    let mut hashes = vec![0; keys.len()].into_boxed_slice();
+    hashing.hash_keys(builder.threads(), 0, keys, &mut hashes);
+
+    let fingerprint = hashes.iter()
+        .fold(keys.len() as u64, |acc, &v| acc.wrapping_add(next_seed(v, 0)));
+
+    let options = [
+        builder.params.alpha.to_bits(),
+        builder.params.lambda.to_bits(),
+        match builder.pilot_width {
+            None => 0,
+            Some(PilotWidth::U8) => 1,
+            Some(PilotWidth::U16) => 2
+        },
+        match builder.fingerprint {
+            None => 0,
+            Some(super::Fingerprint::U8) => 1,
+            Some(super::Fingerprint::U16) => 2
+        },
+        builder.force_build.into()
+    ];
+    let fingerprint = options.iter()
+        .fold(fingerprint, |acc, &v| next_seed(acc ^ v, 0));
+    Some(fingerprint)
+}
 
-        for &bucket_idx in &order {
-            if buckets[bucket_idx as usize].slots.is_empty() {
+/// Wide hashes of keys, if there is a wide hash
+pub(super) fn hash_wide<K>(builder: &MapBuilder<'_, K>, keys: &[K]) -> Option<Box<[u128]>> {
+    if let Some((hash_wide, hash_keys)) = builder.hash_wide_sync {
+        Some(hash_keys(hash_wide, builder.threads(), keys))
+    } else {
+        builder.hash_wide.map(|hash_wide| keys.iter().map(hash_wide).collect())
+    }
+}
+
+/// Hash keys with user hash or wide hash
+fn fingerprints<K>(builder: &MapBuilder<'_, K>, index: &[usize], hashes: &[u64])
+    -> Option<Fingerprints>
+{
+    fn compute<T: Fingerprint>(index: &[usize], hashes: &[u64]) -> Box<[T]> {
+        index.iter()
+            .map(|&idx| T::from_hash(hashes[idx]))
+            .collect()
+    }
+
+    match builder.fingerprint? {
+        super::Fingerprint::U8 => Some(Fingerprints::U8(compute(index, hashes))),
+        super::Fingerprint::U16 => Some(Fingerprints::U16(compute(index, hashes))),
+    }
+}
+
+/// Hash keys in parallel, see [`MapBuilder::set_hash_sync`]
+pub(super) fn hash_keys_sync<K: Sync>(
+    hash: SyncHashFunc<'_, K>,
+    threads: usize,
+    seed: u64,
+    keys: &[K],
+    hashes: &mut [u64]
+) {
+    run_chunks(threads, keys, hashes, |k| hash(seed, k));
+}
+
+/// Hash keys to wide hashes in parallel, see [`MapBuilder::set_hash_wide_sync`]
+pub(super) fn hash_wide_sync<K: Sync>(
+    hash_wide: SyncHashWideFunc<'_, K>,
+    threads: usize,
+    keys: &[K]
+) -> Box<[u128]> {
+    let mut wide = vec![0; keys.len()].into_boxed_slice();
+    run_chunks(threads, keys, &mut wide, hash_wide);
+    wide
+}
+
+/// Fill `output` by chunks of `list` in parallel
+fn run_chunks<T: Sync, U: Send>(threads: usize, list: &[T], output: &mut [U], f: impl Fn(&T) -> U + Sync) {
+    if threads <= 1 {
+        output.iter_mut()
+            .zip(list)
+            .for_each(|(v, t)| *v = f(t));
+        return
+    }
+
+    let chunk_size = list.len().div_ceil(threads).max(1);
+    let f = &f;
+
+    thread::scope(|s| {
+        for (list, output) in list.chunks(chunk_size).zip(output.chunks_mut(chunk_size)) {
+            s.spawn(move || output.iter_mut()
+                .zip(list)
+                .for_each(|(v, t)| *v = f(t))
+            );
+        }
+    });
+}
+
+enum Hashing<'a, K> {
+    Func(HashFunc<'a, K>),
+    Sync(SyncHashFunc<'a, K>, HashKeysFunc<K>),
+    Wide(Cow<'a, [u128]>)
+}
+
+impl<'a, K> Hashing<'a, K> {
+    fn new(builder: &MapBuilder<'a, K>, keys: &[K]) -> Option<Hashing<'a, K>> {
+        if let Some(wide) = builder.wide_hashes {
+            debug_assert_eq!(wide.len(), keys.len());
+            Some(Hashing::Wide(Cow::Borrowed(wide)))
+        } else if let Some(wide) = hash_wide(builder, keys) {
+            Some(Hashing::Wide(Cow::Owned(wide.into_vec())))
+        } else if let Some((hash, hash_keys)) = builder.hash_sync {
+            Some(Hashing::Sync(hash, hash_keys))
+        } else {
+            builder.hash.map(Hashing::Func)
+        }
+    }
+
+    /// Find two keys with the same hash, no seed can separate them
+    ///
+    /// `hashes` is the hash of keys with `seed`.
+    fn check_hashes(&self, builder: &MapBuilder<'_, K>, seed: u64, keys: &[K], hashes: &[u64])
+        -> Result<(), BuildFailed>
+    {
+        let mut sorted = hashes.to_vec();
+        sorted.sort_unstable();
+        let mut same = sorted.windows(2)
+            .filter(|pair| pair[0] == pair[1])
+            .map(|pair| pair[0])
+            .collect::<Vec<_>>();
+
+        if same.is_empty() {
+            return Ok(());
+        }
+
+        same.dedup();
+        let mut candidates = hashes.iter()
+            .enumerate()
+            .filter(|(_, v)| same.binary_search(v).is_ok())
+            .map(|(idx, &v)| (v, idx))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+
+        // Report the first pair by index, so the error does not depend on seed.
+        let mut found: Option<(usize, usize)> = None;
+        for group in candidates.chunk_by(|x, y| x.0 == y.0) {
+            for (i, &(_, x)) in group.iter().enumerate() {
+                for &(_, y) in &group[i + 1..] {
+                    // A 64-bit collision may be accidental, so check again with another seed.
+                    let is_same = match self {
+                        Hashing::Func(hash) => {
+                            let seed = seed ^ GOLDEN;
+                            hash(seed, &keys[x]) == hash(seed, &keys[y])
+                        },
+                        Hashing::Sync(hash, _) => {
+                            let seed = seed ^ GOLDEN;
+                            hash(seed, &keys[x]) == hash(seed, &keys[y])
+                        },
+                        Hashing::Wide(wide) => wide[x] == wide[y]
+                    };
+
+                    // indices in group are ascending
+                    if is_same && found.is_none_or(|pair| (x, y) < pair) {
+                        found = Some((x, y));
+                    }
+                }
+            }
+        }
+
+        match found {
+            Some((first, second)) => Err(match builder.ord {
+                Some(ord) if ord(&keys[first], &keys[second]).is_eq() =>
+                    BuildFailed::DuplicateKey { first, second },
+                _ => BuildFailed::HashCollision { first, second }
+            }),
+            None => Ok(())
+        }
+    }
+
+    fn hash_keys(&self, threads: usize, seed: u64, keys: &[K], hashes: &mut [u64]) {
+        match self {
+            Hashing::Func(hash) => hashes.iter_mut()
+                .zip(keys)
+                .for_each(|(v, k)| *v = hash(seed, k)),
+            Hashing::Sync(hash, hash_keys) => hash_keys(*hash, threads, seed, keys, hashes),
+            Hashing::Wide(wide) => run_chunks(threads, wide, hashes, |&v| phf::remix(seed, v))
+        }
+    }
+}
+
+fn slots_len(params: &PhfParams, keys_len: u32) -> u32 {
+    if params.alpha >= 1.0 {
+        // No spare slots, so there is nothing to remap.
+        return keys_len;
+    }
+
+    let len = (f64::from(keys_len) / params.alpha).ceil() as u32;
+
+    // Avoid powers of two, since then %S does not depend on all bits.
+    len + (len.is_power_of_two() as u32)
+}
+
+fn buckets_len(params: &PhfParams, keys_len: u32) -> u32 {
+    let len = (f64::from(keys_len) / params.lambda).ceil() as u32;
+
+    // Add a few extra buckets to avoid collisions for small n.
+    len + 3
+}
+
+/// Keys index grouped by bucket, ascending in each bucket
+struct Buckets {
+    offsets: Box<[usize]>,
+    keys: Box<[usize]>,
+    ids: Box<[u32]>,
+}
+
+impl Buckets {
+    fn new(buckets_len: u32) -> Buckets {
+        Buckets {
+            offsets: vec![0; buckets_len as usize + 1].into_boxed_slice(),
+            keys: Box::default(),
+            ids: Box::default()
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    fn get(&self, bucket_idx: u32) -> &[usize] {
+        let bucket_idx = bucket_idx as usize;
+        &self.keys[self.offsets[bucket_idx]..self.offsets[bucket_idx + 1]]
+    }
+
+    /// Counting sort keys by bucket, the bucket of keys is computed in parallel
+    fn partition(&mut self, threads: usize, hashes: &[u64]) {
+        let buckets_len: u32 = self.len().try_into().unwrap();
+
+        if self.ids.len() != hashes.len() {
+            self.ids = vec![0; hashes.len()].into_boxed_slice();
+            self.keys = vec![0; hashes.len()].into_boxed_slice();
+        }
+
+        run_chunks(threads, hashes, &mut self.ids, |&v| fast_reduct32(low(v), buckets_len));
+
+        self.offsets.iter_mut().for_each(|n| *n = 0);
+        for &bucket_idx in self.ids.iter() {
+            self.offsets[bucket_idx as usize + 1] += 1;
+        }
+        for i in 1..self.offsets.len() {
+            self.offsets[i] += self.offsets[i - 1];
+        }
+
+        // keep the order of keys in bucket, so the output does not depend on threads
+        let mut cursor = self.offsets[..self.len()].to_vec();
+        for (idx, &bucket_idx) in self.ids.iter().enumerate() {
+            self.keys[cursor[bucket_idx as usize]] = idx;
+            cursor[bucket_idx as usize] += 1;
+        }
+    }
+}
+
+struct Slot {
+    bucket: u32,
+    keys_idx: usize,
+}
+
+/// Pilot search for a single shard
+struct PilotSearch {
+    buckets: Buckets,
+    pilots: Box<[u16]>,
+    pilot_max: u16,
+    order: Box<[u32]>,
+    slots: Vec<Option<Slot>>,
+    stack: Vec<u32>,
+    values_to_add: Vec<(u32, usize)>,
+    recent: Vec<u32>,
+    already_scored: Vec<u32>,
+}
+
+impl PilotSearch {
+    fn new(buckets_len: u32, width: PilotWidth) -> PilotSearch {
+        let lambda = 3;
+
+        PilotSearch {
+            buckets: Buckets::new(buckets_len),
+            pilots: vec![0; buckets_len as usize].into_boxed_slice(),
+            pilot_max: match width {
+                PilotWidth::U8 => u8::MAX.into(),
+                PilotWidth::U16 => u16::MAX
+            },
+            order: (0..buckets_len).collect(),
+            slots: Vec::new(),
+            stack: Vec::new(),
+            // since the number is small enough, we just use naive search
+            values_to_add: Vec::with_capacity(lambda * 2),
+            recent: Vec::new(),
+            already_scored: Vec::new(),
+        }
+    }
+
+    /// Search pilots for the hashes, the slots are kept for [`PilotSearch::remap`].
+    ///
+    /// Returns `false` if this seed needs to be abandoned.
+    fn search(&mut self, threads: usize, seed: u64, hashes: &[u64], slots_len: u32) -> bool {
+        fn reduct(hashes: &[u64], idx: usize, hp: u64, slots_len: u32) -> u32 {
+            fast_reduct32(high(hashes[idx]) ^ high(hp) ^ low(hp), slots_len)
+        }
+
+        let PilotSearch {
+            buckets, pilots, pilot_max, order, slots, stack,
+            values_to_add, recent, already_scored
+        } = self;
+        let pilot_max = *pilot_max;
+
+        pilots.iter_mut().for_each(|p| *p = 0);
+        order.iter_mut().zip(0..).for_each(|(bucket_idx, i)| *bucket_idx = i);
+        slots.clear();
+        slots.resize_with(slots_len as usize, || None);
+
+        buckets.partition(threads, hashes);
+
+        order.sort_unstable_by_key(|&bucket_idx| cmp::Reverse(buckets.get(bucket_idx).len()));
+
+        for &bucket_idx in order.iter() {
+            if buckets.get(bucket_idx).is_empty() {
                 debug_assert_eq!(pilots[bucket_idx as usize], 0);
                 continue
             }
@@ -160,7 +664,7 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
 
             'bucket: while let Some(bucket_idx) = {
                 // big bucket first
-                stack.sort_unstable_by_key(|&bucket_idx| buckets[bucket_idx as usize].slots.len());
+                stack.sort_unstable_by_key(|&bucket_idx| buckets.get(bucket_idx).len());
                 stack.pop()
             } {
                 // Do not evict buckets that have already been evicted.
@@ -169,15 +673,14 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
                 recent.push(bucket_idx);
 
                 // fast search pilot
-                'pilot: for p in 0..=u8::MAX {
+                'pilot: for p in 0..=pilot_max {
                     values_to_add.clear();
 
                     let hp = phf::hash_pilot(seed, p);
 
-                    for (keys_idx, slot_idx) in buckets[bucket_idx as usize]
-                        .slots
+                    for (keys_idx, slot_idx) in buckets.get(bucket_idx)
                         .iter()
-                        .map(|&keys_idx| (keys_idx, reduct(&hashes, keys_idx, hp, slots_len)))
+                        .map(|&keys_idx| (keys_idx, reduct(hashes, keys_idx, hp, slots_len)))
                     {
                         if slots[slot_idx as usize].is_some()
                             || values_to_add.iter().any(|(prev_slot_idx, _)| *prev_slot_idx == slot_idx)
@@ -190,7 +693,7 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
 
                     pilots[bucket_idx as usize] = p;
 
-                    for &(slot_idx, keys_idx) in &values_to_add {
+                    for &(slot_idx, keys_idx) in values_to_add.iter() {
                         slots[slot_idx as usize] = Some(Slot {
                             bucket: bucket_idx,
                             keys_idx 
@@ -203,18 +706,18 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
                 // search best pilot (minimal collisions)
                 let mut best = None;
 
-                'pilot: for p in 0..=u8::MAX {
+                'pilot: for p in 0..=pilot_max {
                     values_to_add.clear();
                     already_scored.clear();
 
                     // start from a slightly different point, just 42 because we don't like random.
-                    let p = p.wrapping_add(0x42);
+                    let p = ((u32::from(p) + 0x42) % (u32::from(pilot_max) + 1)) as u16;
                     let hp = phf::hash_pilot(seed, p);
                     let mut collision_score = 0;
 
-                    for (keys_idx, slot_idx) in buckets[bucket_idx as usize].slots
+                    for (keys_idx, slot_idx) in buckets.get(bucket_idx)
                         .iter()
-                        .map(|&keys_idx| (keys_idx, reduct(&hashes, keys_idx, hp, slots_len)))
+                        .map(|&keys_idx| (keys_idx, reduct(hashes, keys_idx, hp, slots_len)))
                     {
                         if values_to_add.iter().any(|(prev_slot_idx, _)| *prev_slot_idx == slot_idx) {
                             continue 'pilot
@@ -226,7 +729,7 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
                                 continue 'pilot,
                             Some(slot) if !already_scored.contains(&slot.bucket) => {
                                 already_scored.push(slot.bucket);
-                                buckets[slot.bucket as usize].slots.len().pow(2)
+                                buckets.get(slot.bucket).len().pow(2)
                             },
                             Some(_) => 0
                         };
@@ -246,23 +749,21 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
 
                     // Since we already checked for a collision-free solution,
                     // the next best is a single collision of size b_len.
-                    if collision_score == buckets[bucket_idx as usize].slots.len().pow(2) {
+                    if collision_score == buckets.get(bucket_idx).len().pow(2) {
                         break
                     }
                 }
 
                 let Some((_, p)) = best else {
-                    // No available pilot was found, so this seed is abandoned.
-                    seed = next_seed(init_seed, c);
-                    continue 'search
+                    return false
                 };
 
                 pilots[bucket_idx as usize] = p;
                 let hp = phf::hash_pilot(seed, p);
 
-                for (keys_idx, slot_idx) in buckets[bucket_idx as usize].slots
+                for (keys_idx, slot_idx) in buckets.get(bucket_idx)
                     .iter()
-                    .map(|&keys_idx| (keys_idx, reduct(&hashes, keys_idx, hp, slots_len)))
+                    .map(|&keys_idx| (keys_idx, reduct(hashes, keys_idx, hp, slots_len)))
                 {
                     if let Some(old_slot) = slots[slot_idx as usize]
                         .replace(Slot {
@@ -276,9 +777,9 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
                         stack.push(old_slot.bucket);
 
                         let hp = phf::hash_pilot(seed, pilots[old_slot.bucket as usize]);
-                        for old_slot_idx in buckets[old_slot.bucket as usize].slots
+                        for old_slot_idx in buckets.get(old_slot.bucket)
                             .iter()
-                            .map(|&keys_idx| reduct(&hashes, keys_idx, hp, slots_len))
+                            .map(|&keys_idx| reduct(hashes, keys_idx, hp, slots_len))
                             .filter(|&old_slot_idx| old_slot_idx != slot_idx)
                         {
                             debug_assert_eq!(slots[old_slot_idx as usize].as_ref().unwrap().bucket, old_slot.bucket, "{:?}", (bucket_idx, old_slot_idx));
@@ -289,11 +790,18 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
             }
         }
 
-        let mut index = vec![0; keys.len()].into_boxed_slice();
-        let mut remap = vec![0; slots.len() - index.len()].into_boxed_slice();
+        true
+    }
+
+    /// Moves the keys in the slots beyond `keys_len` to the empty slots.
+    ///
+    /// Returns the keys index of each position and the remap of the extra slots.
+    fn remap(&self, keys_len: usize) -> (Box<[usize]>, Box<[u32]>) {
+        let mut index = vec![0; keys_len].into_boxed_slice();
+        let mut remap = vec![0; self.slots.len() - keys_len].into_boxed_slice();
         let mut remap_slots = Vec::new();
 
-        for (slot_idx, slot) in slots.iter().enumerate() {
+        for (slot_idx, slot) in self.slots.iter().enumerate() {
             match (slot_idx.checked_sub(index.len()), slot) {
                 (None, Some(slot)) => index[slot_idx] = slot.keys_idx,
                 (None, None) => remap_slots.push(slot_idx),
@@ -306,13 +814,20 @@ pub(super) fn build_medium<K>(builder: &MapBuilder<'_, K>, keys: &[K])
             }
         }
 
-        return Ok(MapOutput {
-            kind: MapKind::Medium {
-                seed, pilots, remap
-            },
-            index
-        });
+        (index, remap)
+    }
+}
+
+/// Returns the indices of the first pair of equal keys
+pub(super) fn find_duplicate<K: Eq + std::hash::Hash>(keys: &[K]) -> Option<(usize, usize)> {
+    let mut seen = std::collections::HashMap::with_capacity(keys.len());
+
+    for (i, key) in keys.iter().enumerate() {
+        if let Some(&j) = seen.get(key) {
+            return Some((j, i));
+        }
+        seen.insert(key, i);
     }
 
-    Err(BuildFailed("build failed"))
+    None
 }
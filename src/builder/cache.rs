@@ -0,0 +1,95 @@
+use std::{ fs, io };
+use std::path::PathBuf;
+use std::collections::BTreeMap;
+
+/// Seed cache file
+///
+/// Keeps the seed that succeeded for each map, so that the regenerated code
+/// stays the same unless the keys change, see [`MapBuilder::build_cached`](super::MapBuilder::build_cached).
+///
+/// The file is text, one map per line, sorted by name:
+///
+/// ```text
+/// <name> <key set fingerprint in hex> <seed in hex>
+/// ```
+pub struct SeedCache {
+    path: PathBuf,
+    entries: BTreeMap<String, (u64, u64)>,
+    changed: bool,
+}
+
+impl SeedCache {
+    /// Read the cache file, a missing file is an empty cache.
+    pub fn open(path: PathBuf) -> io::Result<SeedCache> {
+        fn invalid(line: &str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, format!("bad seed cache line: {:?}", line))
+        }
+
+        let mut entries = BTreeMap::new();
+
+        let buf = match fs::read_to_string(&path) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err)
+        };
+
+        for line in buf.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let mut iter = line.split_ascii_whitespace();
+            let (Some(name), Some(fingerprint), Some(seed), None) =
+                (iter.next(), iter.next(), iter.next(), iter.next())
+            else {
+                return Err(invalid(line));
+            };
+            let fingerprint = u64::from_str_radix(fingerprint, 16).map_err(|_| invalid(line))?;
+            let seed = u64::from_str_radix(seed, 16).map_err(|_| invalid(line))?;
+
+            entries.insert(name.to_owned(), (fingerprint, seed));
+        }
+
+        Ok(SeedCache { path, entries, changed: false })
+    }
+
+    /// Returns the seed of map if the key set is unchanged.
+    pub fn get(&self, name: &str, fingerprint: u64) -> Option<u64> {
+        self.entries.get(name)
+            .filter(|(fp, _)| *fp == fingerprint)
+            .map(|(_, seed)| *seed)
+    }
+
+    /// Set the seed of map, the name must not contain whitespace.
+    pub fn insert(&mut self, name: &str, fingerprint: u64, seed: u64) {
+        assert!(
+            !name.is_empty() && !name.contains(char::is_whitespace),
+            "bad seed cache name: {:?}", name
+        );
+
+        if self.entries.get(name) != Some(&(fingerprint, seed)) {
+            self.entries.insert(name.to_owned(), (fingerprint, seed));
+            self.changed = true;
+        }
+    }
+
+    /// Write the cache file, it is not touched if nothing changed.
+    pub fn save(&mut self) -> io::Result<()> {
+        use std::fmt::Write;
+
+        if !self.changed {
+            return Ok(());
+        }
+
+        let mut buf = String::from("# precomputed-map seed cache\n");
+        for (name, (fingerprint, seed)) in self.entries.iter() {
+            let _ = writeln!(buf, "{} {:016x} {:016x}", name, fingerprint, seed);
+        }
+
+        fs::write(&self.path, buf)?;
+        self.changed = false;
+        Ok(())
+    }
+}
@@ -3,7 +3,9 @@ use std::io::{ self, Write };
 use std::borrow::Cow;
 use std::path::PathBuf;
 use std::collections::HashMap;
-use super::{ MapOutput, MapKind };
+use crate::container;
+use super::{ MapOutput, MapKind, Pilots, Fingerprints, RangeMapOutput, ContainerWriter };
+use super::encode::{ EliasFano, Packed, Escaped };
 
 /// Code Generator
 ///
@@ -12,6 +14,7 @@ pub struct CodeBuilder<'a> {
     name: String,
     hash: String,
     vis: Option<String>,
+    compress_pilots: bool,
     list: Vec<OutputEntry>,
     u8seq_writer: &'a mut U8SeqWriter,
     u32seq_writer: &'a mut U32SeqWriter,
@@ -61,14 +64,46 @@ enum OutputKind {
         len: usize,
         index: ReferenceId
     },
+    StrPositionSeq {
+        offset: usize,
+        len: usize,
+        index: ReferenceId
+    },
     BytesShortSeq {
         pooled_id: String,
         index: ReferenceId,
     },
+    U16Seq {
+        offset: usize,
+        len: usize
+    },
     U32Seq {
         offset: usize,
         len: usize
     },
+    EliasFanoSeq {
+        len: usize,
+        low_bits: usize,
+        samples: ReferenceId,
+        exceptions: ReferenceId,
+        low: ReferenceId,
+        high: ReferenceId
+    },
+    EscapedSeq {
+        len: usize,
+        bits: usize,
+        size: usize,
+        codes: ReferenceId,
+        samples: ReferenceId,
+        exceptions: ReferenceId
+    },
+    PackedSeq {
+        len: usize,
+        bits: usize,
+        base: u64,
+        item_type: &'static str,
+        words: ReferenceId
+    },
     List {
         item_type: String,
         value: String,
@@ -79,16 +114,47 @@ enum OutputKind {
         keys: ReferenceId,
         values: ReferenceId
     },
-    Tiny(ReferenceId),
+    Keyless {
+        len: usize
+    },
+    Fingerprint {
+        fingerprints: ReferenceId,
+        data: ReferenceId
+    },
+    Tiny {
+        data: ReferenceId,
+        set: bool
+    },
+    Ordered {
+        data: ReferenceId,
+        set: bool
+    },
     Small {
         seed: u64,
-        data: ReferenceId
+        data: ReferenceId,
+        set: bool
     },
     Medium {
         seed: u64,
         pilots: ReferenceId,
         remap: ReferenceId,
-        data: ReferenceId
+        data: ReferenceId,
+        set: bool
+    },
+    Large {
+        seed: u64,
+        key_offsets: ReferenceId,
+        slot_offsets: ReferenceId,
+        shard_retries: ReferenceId,
+        pilots: ReferenceId,
+        remap: ReferenceId,
+        data: ReferenceId,
+        set: bool
+    },
+    Range {
+        starts: ReferenceId,
+        ends: ReferenceId,
+        values: ReferenceId
     }
 }
 
@@ -101,9 +167,10 @@ impl MapOutput {
     /// The seed can be saved and used in next compute to keep output stable.
     pub fn seed(&self) -> Option<u64> {
         match &self.kind {
-            MapKind::Tiny => None,
+            MapKind::Tiny | MapKind::Ordered => None,
             MapKind::Small(seed) => Some(*seed),
-            MapKind::Medium { seed, .. } => Some(*seed)
+            MapKind::Medium { seed, .. } => Some(*seed),
+            MapKind::Large { seed, .. } => Some(*seed)
         }
     }
 
@@ -125,40 +192,53 @@ impl MapOutput {
     /// The provided data must be reordered, otherwise the behavior will be unexpected.
     pub fn create_map(&self, name: String, data: ReferenceId, builder: &mut CodeBuilder)
         -> io::Result<ReferenceId>
+    {
+        self.create_map_raw(name, data, false, builder)
+    }
+
+    /// Create static set
+    ///
+    /// # NOTE
+    ///
+    /// The provided keys must be reordered, otherwise the behavior will be unexpected.
+    pub fn create_set(&self, name: String, keys: ReferenceId, builder: &mut CodeBuilder)
+        -> io::Result<ReferenceId>
+    {
+        self.create_map_raw(name, keys, true, builder)
+    }
+
+    fn create_map_raw(&self, name: String, data: ReferenceId, set: bool, builder: &mut CodeBuilder)
+        -> io::Result<ReferenceId>
     {
         match &self.kind {
             MapKind::Tiny => {
                 let id = builder.list.len();
                 builder.list.push(OutputEntry {
                     name: Some(name),
-                    kind: OutputKind::Tiny(data)
+                    kind: OutputKind::Tiny { data, set }
+                });
+                Ok(ReferenceId(id))
+            },
+            MapKind::Ordered => {
+                let id = builder.list.len();
+                builder.list.push(OutputEntry {
+                    name: Some(name),
+                    kind: OutputKind::Ordered { data, set }
                 });
                 Ok(ReferenceId(id))
             },
             MapKind::Small(seed) => {
+                let data = self.create_fingerprint_raw(data, builder)?;
                 let id = builder.list.len();
                 builder.list.push(OutputEntry {
                     name: Some(name),
-                    kind: OutputKind::Small { seed: *seed, data }
+                    kind: OutputKind::Small { seed: *seed, data, set }
                 });
                 Ok(ReferenceId(id))                
             },
             MapKind::Medium { seed, pilots, remap } => {
-                let pilots = if pilots.len() > 1024 {
-                    let offset = builder.u8seq_writer.count();
-                    builder.u8seq_writer.write_u8seq(pilots)?;
-                    let len = builder.u8seq_writer.count() - offset;
-
-                    let id = builder.list.len();
-                    builder.list.push(OutputEntry {
-                        name: None,
-                        kind: OutputKind::U8Seq { offset, len }
-                    });
-                    ReferenceId(id)
-                } else {
-                    builder.create_list_raw(None, "u8".into(), false, pilots.iter().copied())?
-                };
-
+                let data = self.create_fingerprint_raw(data, builder)?;
+                let pilots = builder.create_pilots_raw(pilots)?;
                 let remap = builder.create_u32_seq_raw(None, remap.iter().copied())?;
 
                 let id = builder.list.len();
@@ -166,13 +246,69 @@ impl MapOutput {
                     name: Some(name),
                     kind: OutputKind::Medium {
                         seed: *seed,
-                        pilots, remap, data
+                        pilots, remap, data, set
+                    }
+                });
+                Ok(ReferenceId(id))
+            },
+            MapKind::Large { seed, key_offsets, slot_offsets, shard_retries, pilots, remap } => {
+                let data = self.create_fingerprint_raw(data, builder)?;
+                let key_offsets = builder.create_u32_seq_raw(None, key_offsets.iter().copied())?;
+                let slot_offsets = builder.create_u32_seq_raw(None, slot_offsets.iter().copied())?;
+                let shard_retries = builder.create_u32_seq_raw(None, shard_retries.iter().copied())?;
+                let pilots = builder.create_pilots_raw(pilots)?;
+                let remap = builder.create_u32_seq_raw(None, remap.iter().copied())?;
+
+                let id = builder.list.len();
+                builder.list.push(OutputEntry {
+                    name: Some(name),
+                    kind: OutputKind::Large {
+                        seed: *seed,
+                        key_offsets, slot_offsets, shard_retries, pilots, remap, data, set
                     }
                 });
                 Ok(ReferenceId(id))
             },
         }
     }
+
+    fn create_fingerprint_raw(&self, data: ReferenceId, builder: &mut CodeBuilder)
+        -> io::Result<ReferenceId>
+    {
+        let fingerprints = match &self.fingerprints {
+            None => return Ok(data),
+            Some(Fingerprints::U8(list)) => builder.create_u8_seq_raw(None, list)?,
+            Some(Fingerprints::U16(list)) => builder.create_u16_seq_raw(None, list)?,
+        };
+
+        let id = builder.list.len();
+        builder.list.push(OutputEntry {
+            name: None,
+            kind: OutputKind::Fingerprint { fingerprints, data }
+        });
+        Ok(ReferenceId(id))
+    }
+}
+
+impl<V> RangeMapOutput<V> {
+    /// Create static range map
+    ///
+    /// # NOTE
+    ///
+    /// The provided values must be in the order of [`RangeMapOutput::values`].
+    pub fn create_map(&self, name: String, values: ReferenceId, builder: &mut CodeBuilder)
+        -> io::Result<ReferenceId>
+    {
+        let starts = builder.create_u32_seq_raw(None, self.starts.iter().copied())?;
+        let ends = builder.create_u32_seq_raw(None, self.ends.iter().copied())?;
+
+        let id = builder.list.len();
+        builder.list.push(OutputEntry {
+            name: Some(name),
+            kind: OutputKind::Range { starts, ends, values }
+        });
+        Ok(ReferenceId(id))
+    }
 }
 
 impl<'a> CodeBuilder<'a> {
@@ -190,6 +326,7 @@ impl<'a> CodeBuilder<'a> {
         CodeBuilder {
             name, hash,
             vis: None,
+            compress_pilots: false,
             list: Vec::new(),
             u8seq_writer,
             u32seq_writer,
@@ -201,6 +338,19 @@ impl<'a> CodeBuilder<'a> {
         self.vis = vis;
     }
 
+    /// Store pilots of medium and large maps as escape coded seq.
+    ///
+    /// It is only used if it is smaller than raw pilots,
+    /// and the size savings is written as a comment in generated code.
+    /// The savings depends on how skewed the pilots are,
+    /// it is usually small for the default parameters.
+    ///
+    /// Only u8 pilots are compressed, u16 pilots are always stored raw,
+    /// see [`MapBuilder::set_pilot_width`](super::MapBuilder::set_pilot_width).
+    pub fn set_compress_pilots(&mut self, flag: bool) {
+        self.compress_pilots = flag;
+    }
+
     pub fn create_custom(&mut self, name: String) -> ReferenceId {
         let id = self.list.len();
         self.list.push(OutputEntry {
@@ -268,6 +418,23 @@ impl<'a> CodeBuilder<'a> {
         ReferenceId(id)
     }
 
+    /// Create keyless store
+    ///
+    /// The map created with it can only be queried with `index_of`,
+    /// tiny and ordered maps need keys, so they are not supported.
+    pub fn create_keyless(&mut self, mapout: &MapOutput) -> io::Result<ReferenceId> {
+        if matches!(mapout.kind, MapKind::Tiny | MapKind::Ordered) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "keyless map requires hash"));
+        }
+
+        let id = self.list.len();
+        self.list.push(OutputEntry {
+            name: None,
+            kind: OutputKind::Keyless { len: mapout.index.len() }
+        });
+        Ok(ReferenceId(id))
+    }
+
     pub fn create_bytes_keys<SEQ, B>(&mut self, name: String, mapout: &MapOutput, seq: SEQ)
         -> io::Result<ReferenceId>
     where
@@ -286,11 +453,62 @@ impl<'a> CodeBuilder<'a> {
         }
     }
 
+    /// Same as [`create_bytes_keys`](Self::create_bytes_keys), but the keys are `&'static str`.
+    pub fn create_str_keys<SEQ, B>(&mut self, name: String, mapout: &MapOutput, seq: SEQ)
+        -> io::Result<ReferenceId>
+    where
+        SEQ: Iterator<Item = B> + ExactSizeIterator,
+        B: AsRef<str>
+    {
+        if seq.len() > 16 {
+            self.create_str_position_seq(name, seq)
+        } else {
+            self.create_list_raw(
+                Some(name),
+                "&'static str".into(),
+                matches!(mapout.kind, MapKind::Tiny),
+                seq.map(|b| format!("{:?}", b.as_ref()))
+            )
+        }
+    }
+
     pub fn create_bytes_position_seq<SEQ, B>(&mut self, name: String, seq: SEQ)
         -> io::Result<ReferenceId>
     where
         SEQ: Iterator<Item = B> + ExactSizeIterator,
         B: AsRef<[u8]>
+    {
+        let (offset, len, index) = self.create_position_seq_raw(seq)?;
+
+        let id = self.list.len();
+        self.list.push(OutputEntry {
+            name: Some(name),
+            kind: OutputKind::BytesPositionSeq { offset, len, index }
+        });
+        Ok(ReferenceId(id))
+    }
+
+    pub fn create_str_position_seq<SEQ, B>(&mut self, name: String, seq: SEQ)
+        -> io::Result<ReferenceId>
+    where
+        SEQ: Iterator<Item = B> + ExactSizeIterator,
+        B: AsRef<str>
+    {
+        let (offset, len, index) = self.create_position_seq_raw(seq.map(StrBytes))?;
+
+        let id = self.list.len();
+        self.list.push(OutputEntry {
+            name: Some(name),
+            kind: OutputKind::StrPositionSeq { offset, len, index }
+        });
+        Ok(ReferenceId(id))
+    }
+
+    fn create_position_seq_raw<SEQ, B>(&mut self, seq: SEQ)
+        -> io::Result<(usize, usize, ReferenceId)>
+    where
+        SEQ: Iterator<Item = B>,
+        B: AsRef<[u8]>
     {
         let offset = self.u8seq_writer.count();
         let mut count = 0;
@@ -304,14 +522,48 @@ impl<'a> CodeBuilder<'a> {
             list.push(count);
         }
         let len = self.u8seq_writer.count() - offset;
-        let index = self.create_u32_seq_raw(None, list.iter().copied())?;
+        let index = self.create_monotone_seq_raw(None, &list)?;
+        Ok((offset, len, index))
+    }
 
-        let id = self.list.len();
-        self.list.push(OutputEntry {
-            name: Some(name),
-            kind: OutputKind::BytesPositionSeq { offset, len, index }
-        });
-        Ok(ReferenceId(id))
+    fn create_u8_seq_raw(&mut self, name: Option<String>, seq: &[u8])
+        -> io::Result<ReferenceId>
+    {
+        if seq.len() > 1024 {
+            let offset = self.u8seq_writer.count();
+            self.u8seq_writer.write_u8seq(seq)?;
+            let len = self.u8seq_writer.count() - offset;
+
+            let id = self.list.len();
+            self.list.push(OutputEntry {
+                name,
+                kind: OutputKind::U8Seq { offset, len }
+            });
+            Ok(ReferenceId(id))
+        } else {
+            self.create_list_raw(name, "u8".into(), false, seq.iter().copied())
+        }
+    }
+
+    fn create_u16_seq_raw(&mut self, name: Option<String>, seq: &[u16])
+        -> io::Result<ReferenceId>
+    {
+        if seq.len() > 512 {
+            let offset = self.u8seq_writer.count();
+            for n in seq {
+                self.u8seq_writer.write_u8seq(&n.to_le_bytes())?;
+            }
+            let len = self.u8seq_writer.count() - offset;
+
+            let id = self.list.len();
+            self.list.push(OutputEntry {
+                name,
+                kind: OutputKind::U16Seq { offset, len }
+            });
+            Ok(ReferenceId(id))
+        } else {
+            self.create_list_raw(name, "u16".into(), false, seq.iter().copied())
+        }
     }
 
     fn create_u32_seq_raw<SEQ>(&mut self, name: Option<String>, seq: SEQ)
@@ -337,6 +589,32 @@ impl<'a> CodeBuilder<'a> {
         }        
     }    
 
+    /// Create monotone u32 seq, it is elias-fano encoded if that is smaller.
+    fn create_monotone_seq_raw(&mut self, name: Option<String>, list: &[u32])
+        -> io::Result<ReferenceId>
+    {
+        match EliasFano::encode(list) {
+            Some(ef) if ef.size() < list.len() * 4 => {
+                let samples = self.create_u32_seq_raw(None, ef.samples.iter().copied())?;
+                let exceptions = self.create_u32_seq_raw(None, ef.exceptions.iter().copied())?;
+                let low = self.create_u32_seq_raw(None, ef.low.iter().copied())?;
+                let high = self.create_u32_seq_raw(None, ef.high.iter().copied())?;
+
+                let id = self.list.len();
+                self.list.push(OutputEntry {
+                    name,
+                    kind: OutputKind::EliasFanoSeq {
+                        len: list.len(),
+                        low_bits: ef.low_bits,
+                        samples, exceptions, low, high
+                    }
+                });
+                Ok(ReferenceId(id))
+            },
+            _ => self.create_u32_seq_raw(name, list.iter().copied())
+        }
+    }
+
     pub fn create_u32_seq<SEQ>(&mut self, name: String, seq: SEQ)
         -> io::Result<ReferenceId>
     where
@@ -345,6 +623,87 @@ impl<'a> CodeBuilder<'a> {
         self.create_u32_seq_raw(Some(name), seq)
     }
 
+    fn create_pilots_raw(&mut self, pilots: &Pilots) -> io::Result<ReferenceId> {
+        let pilots = match pilots {
+            Pilots::U8(pilots) => pilots,
+            // The escape code is for u8 pilots, u16 pilots are stored raw even if compression is enabled.
+            Pilots::U16(pilots) => return self.create_u16_seq_raw(None, pilots)
+        };
+
+        if !self.compress_pilots {
+            return self.create_u8_seq_raw(None, pilots);
+        }
+
+        let escaped = Escaped::encode(pilots);
+        if escaped.size() >= pilots.len() {
+            return self.create_u8_seq_raw(None, pilots);
+        }
+
+        let codes = self.create_u32_seq_raw(None, escaped.codes.iter().copied())?;
+        let samples = self.create_u32_seq_raw(None, escaped.samples.iter().copied())?;
+        let exceptions = self.create_u8_seq_raw(None, &escaped.exceptions)?;
+
+        let id = self.list.len();
+        self.list.push(OutputEntry {
+            name: None,
+            kind: OutputKind::EscapedSeq {
+                len: pilots.len(),
+                bits: escaped.bits,
+                size: escaped.size(),
+                codes, samples, exceptions
+            }
+        });
+        Ok(ReferenceId(id))
+    }
+
+    fn create_packed_seq_raw(
+        &mut self,
+        name: Option<String>,
+        item_type: &'static str,
+        list: &[u64]
+    )
+        -> io::Result<ReferenceId>
+    {
+        let packed = Packed::encode(list);
+        let words = self.create_u32_seq_raw(None, packed.words.iter().copied())?;
+
+        let id = self.list.len();
+        self.list.push(OutputEntry {
+            name,
+            kind: OutputKind::PackedSeq {
+                len: list.len(),
+                bits: packed.bits,
+                base: packed.base,
+                item_type,
+                words
+            }
+        });
+        Ok(ReferenceId(id))
+    }
+
+    /// Create bit packed u32 seq
+    ///
+    /// The minimal width is picked, and the minimum value is subtracted
+    /// as base offset if that makes it narrower.
+    pub fn create_packed_seq<SEQ>(&mut self, name: String, seq: SEQ)
+        -> io::Result<ReferenceId>
+    where
+        SEQ: Iterator<Item = u32> + ExactSizeIterator
+    {
+        let list = seq.map(u64::from).collect::<Vec<_>>();
+        self.create_packed_seq_raw(Some(name), "u32", &list)
+    }
+
+    /// Create bit packed u64 seq, see [`create_packed_seq`](Self::create_packed_seq).
+    pub fn create_packed_u64_seq<SEQ>(&mut self, name: String, seq: SEQ)
+        -> io::Result<ReferenceId>
+    where
+        SEQ: Iterator<Item = u64> + ExactSizeIterator
+    {
+        let list = seq.collect::<Vec<_>>();
+        self.create_packed_seq_raw(Some(name), "u64", &list)
+    }
+
     pub fn create_short_id_seq<SEQ>(&mut self, name: String, pool: &ShortPool<'_>, seq: SEQ)
         -> io::Result<ReferenceId>
     where
@@ -361,6 +720,97 @@ impl<'a> CodeBuilder<'a> {
         Ok(ReferenceId(id))
     }
 
+    /// Write the blobs into a container, with a text manifest of entries.
+    ///
+    /// The manifest has one line per entry, in the order of creation,
+    /// such as `u8seq <id> <offset> <len>` or `map <id> medium <seed> <pilots> <remap> <data>`,
+    /// so the blobs can be inspected without the generated code.
+    /// The id is the name of entry, or `#<index>` for unnamed entries,
+    /// and the entries referenced by another one are written as their id.
+    ///
+    /// The seed of container header is the seed of the first small, medium or large map,
+    /// or `0` if there is none.
+    pub fn write_container(&mut self, hash_id: u32, writer: &mut dyn io::Write) -> io::Result<()> {
+        use std::fmt::Write;
+
+        let id = |ReferenceId(idx): &ReferenceId| match self.list[*idx].name.as_ref() {
+            Some(name) => name.clone(),
+            None => format!("#{}", idx)
+        };
+        let map = |set: &bool| if *set { "set" } else { "map" };
+        let mut manifest = String::new();
+        let mut seed = None;
+
+        for (idx, entry) in self.list.iter().enumerate() {
+            let name = id(&ReferenceId(idx));
+
+            let _ = match &entry.kind {
+                OutputKind::Custom { .. } => writeln!(manifest, "custom {}", name),
+                OutputKind::U8Seq { offset, len } =>
+                    writeln!(manifest, "u8seq {} {} {}", name, offset, len),
+                OutputKind::U16Seq { offset, len } =>
+                    writeln!(manifest, "u16seq {} {} {}", name, offset, len),
+                OutputKind::U32Seq { offset, len } =>
+                    writeln!(manifest, "u32seq {} {} {}", name, offset, len),
+                OutputKind::BytesPositionSeq { offset, len, index } =>
+                    writeln!(manifest, "bytes {} {} {} {}", name, offset, len, id(index)),
+                OutputKind::StrPositionSeq { offset, len, index } =>
+                    writeln!(manifest, "str {} {} {} {}", name, offset, len, id(index)),
+                OutputKind::BytesShortSeq { pooled_id, index } =>
+                    writeln!(manifest, "short {} {} {}", name, pooled_id, id(index)),
+                OutputKind::EliasFanoSeq { len, low_bits, samples, exceptions, low, high } =>
+                    writeln!(
+                        manifest, "elias_fano {} {} {} {} {} {} {}",
+                        name, len, low_bits, id(samples), id(exceptions), id(low), id(high)
+                    ),
+                OutputKind::EscapedSeq { len, bits, size, codes, samples, exceptions } =>
+                    writeln!(manifest, "escaped {} {} {} {} {} {} {}", name, len, bits, size, id(codes), id(samples), id(exceptions)),
+                OutputKind::PackedSeq { len, bits, base, item_type, words } =>
+                    writeln!(manifest, "packed {} {} {} {} {} {}", name, item_type, len, bits, base, id(words)),
+                OutputKind::List { len, .. } => writeln!(manifest, "list {} {}", name, len),
+                OutputKind::Pair { keys, values } =>
+                    writeln!(manifest, "pair {} {} {}", name, id(keys), id(values)),
+                OutputKind::Keyless { len } => writeln!(manifest, "keyless {} {}", name, len),
+                OutputKind::Fingerprint { fingerprints, data } =>
+                    writeln!(manifest, "fingerprint {} {} {}", name, id(fingerprints), id(data)),
+                OutputKind::Tiny { data, set } =>
+                    writeln!(manifest, "{} {} tiny {}", map(set), name, id(data)),
+                OutputKind::Ordered { data, set } =>
+                    writeln!(manifest, "{} {} ordered {}", map(set), name, id(data)),
+                OutputKind::Range { starts, ends, values } =>
+                    writeln!(manifest, "map {} range {} {} {}", name, id(starts), id(ends), id(values)),
+                OutputKind::Small { seed: map_seed, data, set } => {
+                    seed.get_or_insert(*map_seed);
+                    writeln!(manifest, "{} {} small {} {}", map(set), name, map_seed, id(data))
+                },
+                OutputKind::Medium { seed: map_seed, pilots, remap, data, set } => {
+                    seed.get_or_insert(*map_seed);
+                    writeln!(
+                        manifest, "{} {} medium {} {} {} {}",
+                        map(set), name, map_seed, id(pilots), id(remap), id(data)
+                    )
+                },
+                OutputKind::Large { seed: map_seed, key_offsets, slot_offsets, shard_retries, pilots, remap, data, set } => {
+                    seed.get_or_insert(*map_seed);
+                    writeln!(
+                        manifest, "{} {} large {} {} {} {} {} {} {}",
+                        map(set), name, map_seed, id(key_offsets), id(slot_offsets),
+                        id(shard_retries), id(pilots), id(remap), id(data)
+                    )
+                }
+            };
+        }
+
+        let u8seq = self.u8seq_writer.0.read_all()?;
+        let u32seq = self.u32seq_writer.0.read_all()?;
+
+        ContainerWriter::new(hash_id, seed.unwrap_or_default())
+            .push_section(container::SECTION_U8SEQ, u8seq)
+            .push_section(container::SECTION_U32SEQ, u32seq)
+            .push_section(container::SECTION_MANIFEST, manifest.into_bytes())
+            .write(writer)
+    }
+
     pub fn codegen(self, writer: &mut dyn io::Write) -> io::Result<()> {
         struct ReferenceEntry {
             name: String,
@@ -395,6 +845,22 @@ impl<'a> CodeBuilder<'a> {
                         ReferenceEntry { name: ty }
                     }
                 },
+                OutputKind::U16Seq { offset, len } => {
+                    let data_ty = format!(
+                        "{crate_name}::store::SliceData<{}, {}, {}>",
+                        offset,
+                        len,
+                        u8seq_name,
+                    );
+                    let ty = format!("{crate_name}::seq::U16Seq<{}>", data_ty);
+
+                    if let Some(entry_name) = entry.name.as_ref() {
+                        writeln!(writer, "{vis}type {} = {};", entry_name, ty)?;
+                        ReferenceEntry { name: entry_name.clone() }
+                    } else {
+                        ReferenceEntry { name: ty }
+                    }
+                },
                 OutputKind::U32Seq { offset, len } => {
                     let data_ty = format!(
                         "{crate_name}::store::SliceData<{}, {}, {}>",
@@ -415,13 +881,80 @@ impl<'a> CodeBuilder<'a> {
                         ReferenceEntry { name: ty }
                     }
                 },
-                OutputKind::BytesPositionSeq { offset, len, index } => {
+                OutputKind::EliasFanoSeq { len, low_bits, samples, exceptions, low, high } => {
+                    let ty = format!(
+                        "{crate_name}::seq::EliasFanoSeq<{}, {}, {}, {}, {}, {}>",
+                        len,
+                        low_bits,
+                        &list[samples.0].name,
+                        &list[exceptions.0].name,
+                        &list[low.0].name,
+                        &list[high.0].name,
+                    );
+
+                    if let Some(entry_name) = entry.name.as_ref() {
+                        writeln!(writer, "{vis}type {} = {};", entry_name, ty)?;
+                        ReferenceEntry { name: entry_name.clone() }
+                    } else {
+                        ReferenceEntry { name: ty }
+                    }
+                },
+                OutputKind::EscapedSeq { len, bits, size, codes, samples, exceptions } => {
+                    let ty = format!(
+                        "{crate_name}::seq::EscapedSeq<{}, {}, {}, {}, {}>",
+                        len,
+                        bits,
+                        &list[codes.0].name,
+                        &list[samples.0].name,
+                        &list[exceptions.0].name,
+                    );
+
+                    writeln!(
+                        writer,
+                        "// compressed pilots: {} -> {} bytes ({:.1}%)",
+                        len,
+                        size,
+                        *size as f64 * 100.0 / *len as f64
+                    )?;
+
+                    if let Some(entry_name) = entry.name.as_ref() {
+                        writeln!(writer, "{vis}type {} = {};", entry_name, ty)?;
+                        ReferenceEntry { name: entry_name.clone() }
+                    } else {
+                        ReferenceEntry { name: ty }
+                    }
+                },
+                OutputKind::PackedSeq { len, bits, base, item_type, words } => {
+                    let ty = format!(
+                        "{crate_name}::seq::PackedSeq<{}, {}, {}, {}, {}>",
+                        len,
+                        bits,
+                        base,
+                        item_type,
+                        &list[words.0].name,
+                    );
+
+                    if let Some(entry_name) = entry.name.as_ref() {
+                        writeln!(writer, "{vis}type {} = {};", entry_name, ty)?;
+                        ReferenceEntry { name: entry_name.clone() }
+                    } else {
+                        ReferenceEntry { name: ty }
+                    }
+                },
+                OutputKind::BytesPositionSeq { offset, len, index }
+                    | OutputKind::StrPositionSeq { offset, len, index } =>
+                {
+                    let seq_ty = match &entry.kind {
+                        OutputKind::StrPositionSeq { .. } => "StrPositionSeq",
+                        _ => "PositionSeq"
+                    };
                     let data_ty = format!(
                         "{crate_name}::store::SliceData<{}, {}, {}>",
                         offset, len, u8seq_name
                     );
                     let ty = format!(
-                        "{crate_name}::seq::PositionSeq<{}, {}>",
+                        "{crate_name}::seq::{}<{}, {}>",
+                        seq_ty,
                         &list[index.0].name,
                         data_ty,
                     );
@@ -468,25 +1001,68 @@ impl<'a> CodeBuilder<'a> {
                     );
                     ReferenceEntry { name: ty }                    
                 }
-                OutputKind::Tiny(data) => {
+                OutputKind::Keyless { len } => ReferenceEntry {
+                    name: format!("{crate_name}::store::Keyless<{}>", len),
+                },
+                OutputKind::Fingerprint { fingerprints, data } => {
+                    let ty = format!(
+                        "{crate_name}::store::WithFingerprint<{}, {}>",
+                        &list[fingerprints.0].name,
+                        &list[data.0].name,
+                    );
+                    ReferenceEntry { name: ty }
+                }
+                OutputKind::Tiny { data, set } => {
+                    let kind = if *set { "Set" } else { "Map" };
+                    let ty = format!(
+                        "{crate_name}::Tiny{kind}<{}>",
+                        &list[data.0].name
+                    );
+                    let val = format!("{crate_name}::Tiny{kind}::new()");
+
+                    let entry_name = entry.name.as_ref().unwrap();
+                    writeln!(writer, "{vis}const {}: {} = {};", entry_name, ty, val)?;
+                    ReferenceEntry { name: entry_name.clone() }
+                },
+                OutputKind::Ordered { data, set } => {
+                    let kind = if *set { "Set" } else { "Map" };
                     let ty = format!(
-                        "{crate_name}::TinyMap<{}>",
+                        "{crate_name}::Ordered{kind}<{}>",
                         &list[data.0].name
                     );
-                    let val = format!("{crate_name}::TinyMap::new()");
+                    let val = format!("{crate_name}::Ordered{kind}::new()");
+
+                    let entry_name = entry.name.as_ref().unwrap();
+                    writeln!(writer, "{vis}const {}: {} = {};", entry_name, ty, val)?;
+                    ReferenceEntry { name: entry_name.clone() }
+                },
+                OutputKind::Small { seed, data, set } => {
+                    let kind = if *set { "Set" } else { "Map" };
+                    let ty = format!(
+                        "{crate_name}::Small{kind}<{}, {}>",
+                        &list[data.0].name,
+                        self.hash,
+                    );
+                    let val = format!(
+                        "{crate_name}::Small{kind}::new({})",
+                        seed,
+                    );
 
                     let entry_name = entry.name.as_ref().unwrap();
                     writeln!(writer, "{vis}const {}: {} = {};", entry_name, ty, val)?;
                     ReferenceEntry { name: entry_name.clone() }
                 },
-                OutputKind::Small { seed, data } => {
+                OutputKind::Medium { seed, pilots, remap, data, set } => {
+                    let kind = if *set { "Set" } else { "Map" };
                     let ty = format!(
-                        "{crate_name}::SmallMap<{}, {}>",
+                        "{crate_name}::Medium{kind}<{}, {}, {}, {}>",
+                        &list[pilots.0].name,
+                        &list[remap.0].name,
                         &list[data.0].name,
                         self.hash,
                     );
                     let val = format!(
-                        "{crate_name}::SmallMap::new({})",
+                        "{crate_name}::Medium{kind}::new({})",
                         seed,
                     );
 
@@ -494,19 +1070,36 @@ impl<'a> CodeBuilder<'a> {
                     writeln!(writer, "{vis}const {}: {} = {};", entry_name, ty, val)?;
                     ReferenceEntry { name: entry_name.clone() }
                 },
-                OutputKind::Medium { seed, pilots, remap, data } => {
+                OutputKind::Large { seed, key_offsets, slot_offsets, shard_retries, pilots, remap, data, set } => {
+                    let kind = if *set { "Set" } else { "Map" };
                     let ty = format!(
-                        "{crate_name}::MediumMap<{}, {}, {}, {}>",
+                        "{crate_name}::Large{kind}<{}, {}, {}, {}, {}, {}, {}>",
+                        &list[key_offsets.0].name,
+                        &list[slot_offsets.0].name,
+                        &list[shard_retries.0].name,
                         &list[pilots.0].name,
                         &list[remap.0].name,
                         &list[data.0].name,
                         self.hash,
                     );
                     let val = format!(
-                        "{crate_name}::MediumMap::new({})",
+                        "{crate_name}::Large{kind}::new({})",
                         seed,
                     );
 
+                    let entry_name = entry.name.as_ref().unwrap();
+                    writeln!(writer, "{vis}const {}: {} = {};", entry_name, ty, val)?;
+                    ReferenceEntry { name: entry_name.clone() }
+                },
+                OutputKind::Range { starts, ends, values } => {
+                    let ty = format!(
+                        "{crate_name}::RangeMap<{}, {}, {}>",
+                        &list[starts.0].name,
+                        &list[ends.0].name,
+                        &list[values.0].name,
+                    );
+                    let val = format!("{crate_name}::RangeMap::new()");
+
                     let entry_name = entry.name.as_ref().unwrap();
                     writeln!(writer, "{vis}const {}: {} = {};", entry_name, ty, val)?;
                     ReferenceEntry { name: entry_name.clone() }
@@ -539,16 +1132,26 @@ impl<W: io::Write> io::Write for CountWriter<W> {
 }
 
 impl BytesWriter {
+    fn read_all(&mut self) -> io::Result<Vec<u8>> {
+        match self.writer.as_mut() {
+            Some(writer) => {
+                writer.flush()?;
+                fs::read(&self.file)
+            },
+            None => Ok(Vec::new())
+        }
+    }
+
     fn writer(&mut self) -> io::Result<&mut CountWriter<fs::File>> {
-        if self.writer.is_some() {
-            Ok(self.writer.as_mut().unwrap())
-        } else {
+        if self.writer.is_none() {
             let fd = fs::File::create_new(&self.file)?;
-            Ok(self.writer.get_or_insert(CountWriter {
+            self.writer = Some(CountWriter {
                 writer: fd,
                 count: 0
-            }))
+            });
         }
+
+        Ok(self.writer.as_mut().unwrap())
     }
 }
 
@@ -647,7 +1250,7 @@ impl<'s> ShortPool<'s> {
     }
 
     pub fn get(&self, id: ShortId) -> &[u8] {
-        let (offset, len) = crate::seq::pooled_unpack(id.0);
+        let (offset, len) = crate::seq::pooled_unpack(id.0).unwrap();
         &self.buf[offset..][..len]
     }
 
@@ -680,7 +1283,7 @@ impl {crate_name}::seq::PooledId for {name} {{
     fn get(self) -> Option<&'static [u8]> {{
         use {crate_name}::store::AsData;
     
-        let (offset, len) = {crate_name}::seq::pooled_unpack(self.0);
+        let (offset, len) = {crate_name}::seq::pooled_unpack(self.0)?;
         <{crate_name}::store::SliceData<{data_offset}, {data_len}, {u8seq}>>::as_data()
             .get(offset..offset + len)
     }}
@@ -691,3 +1294,11 @@ impl {crate_name}::seq::PooledId for {name} {{
         )
     }
 }
+
+struct StrBytes<B>(B);
+
+impl<B: AsRef<str>> AsRef<[u8]> for StrBytes<B> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref().as_bytes()
+    }
+}
@@ -0,0 +1,56 @@
+use std::io;
+use std::borrow::Cow;
+use crate::container::{ MAGIC, VERSION, HEADER_LEN, ENTRY_LEN, crc32 };
+
+/// Container writer
+///
+/// Writes the sections in the format of [`container`](crate::container).
+pub struct ContainerWriter<'a> {
+    hash_id: u32,
+    seed: u64,
+    sections: Vec<(u32, Cow<'a, [u8]>)>,
+}
+
+impl<'a> ContainerWriter<'a> {
+    /// `hash_id` is defined by user, it is used to check that the reader uses the same hash.
+    pub fn new(hash_id: u32, seed: u64) -> Self {
+        ContainerWriter {
+            hash_id, seed,
+            sections: Vec::new()
+        }
+    }
+
+    pub fn push_section(&mut self, tag: u32, data: impl Into<Cow<'a, [u8]>>) -> &mut Self {
+        self.sections.push((tag, data.into()));
+        self
+    }
+
+    pub fn write(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        fn to_u32(n: usize) -> io::Result<u32> {
+            n.try_into().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "container too large"))
+        }
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.hash_id.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&to_u32(self.sections.len())?.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        let mut offset = HEADER_LEN + self.sections.len() * ENTRY_LEN;
+        for (tag, data) in self.sections.iter() {
+            writer.write_all(&tag.to_le_bytes())?;
+            writer.write_all(&to_u32(offset)?.to_le_bytes())?;
+            writer.write_all(&to_u32(data.len())?.to_le_bytes())?;
+            writer.write_all(&crc32(data).to_le_bytes())?;
+            offset += data.len();
+        }
+        to_u32(offset)?;
+
+        for (_, data) in self.sections.iter() {
+            writer.write_all(data)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::io;
+use crate::container;
+use crate::dynamic::{ KIND_TINY, KIND_SMALL, KIND_MEDIUM };
+use super::{ MapOutput, MapKind, Pilots, ContainerWriter };
+
+impl MapOutput {
+    /// Write a self-contained data file of map
+    ///
+    /// It can be loaded at runtime by [`DynMap`](crate::dynamic::DynMap),
+    /// the keys must be hashed as bytes by the `HashOne` used at runtime,
+    /// `hash_id` is stored in container to identify it.
+    ///
+    /// The provided keys and values are in the original order, not reordered.
+    /// Only tiny, small and medium maps are supported,
+    /// and keys of tiny map must be sorted in bytes order.
+    pub fn write_dyn_map<K, V>(
+        &self,
+        hash_id: u32,
+        keys: &[K],
+        values: &[V],
+        writer: &mut dyn io::Write
+    )
+        -> io::Result<()>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>
+    {
+        fn invalid(msg: &'static str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidInput, msg)
+        }
+
+        fn to_u32(n: usize) -> io::Result<u32> {
+            n.try_into().map_err(|_| invalid("dyn map too large"))
+        }
+
+        fn ends<'a>(seq: impl Iterator<Item = &'a [u8]>) -> io::Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            let mut end = 0;
+            for b in seq {
+                end += b.len();
+                buf.extend_from_slice(&to_u32(end)?.to_le_bytes());
+            }
+            Ok(buf)
+        }
+
+        assert_eq!(keys.len(), values.len());
+
+        let (kind, seed, pilots, remap): (_, _, Option<&Pilots>, &[u32]) = match &self.kind {
+            MapKind::Tiny => {
+                let is_sorted = self.reorder(keys)
+                    .map(AsRef::as_ref)
+                    .is_sorted();
+
+                if !is_sorted {
+                    return Err(invalid("tiny map keys are not in bytes order"));
+                }
+
+                (KIND_TINY, 0, None, &[])
+            },
+            MapKind::Small(seed) => (KIND_SMALL, *seed, None, &[]),
+            MapKind::Medium { seed, pilots, remap } => (KIND_MEDIUM, *seed, Some(pilots), remap),
+            MapKind::Ordered => return Err(invalid("ordered map is unsupported by dyn map")),
+            MapKind::Large { .. } => return Err(invalid("large map is unsupported by dyn map"))
+        };
+
+        let (pilot_width, pilots) = match pilots {
+            None => (1u32, Vec::new()),
+            Some(Pilots::U8(pilots)) => (1, pilots.to_vec()),
+            Some(Pilots::U16(pilots)) => (2, pilots.iter().flat_map(|n| n.to_le_bytes()).collect())
+        };
+
+        let mut meta = Vec::with_capacity(12);
+        meta.extend_from_slice(&kind.to_le_bytes());
+        meta.extend_from_slice(&to_u32(keys.len())?.to_le_bytes());
+        meta.extend_from_slice(&pilot_width.to_le_bytes());
+
+        let remap = remap.iter()
+            .flat_map(|n| n.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let keys_buf = self.reorder(keys)
+            .flat_map(|b| b.as_ref().iter().copied())
+            .collect::<Vec<u8>>();
+        let values_buf = self.reorder(values)
+            .flat_map(|b| b.as_ref().iter().copied())
+            .collect::<Vec<u8>>();
+
+        ContainerWriter::new(hash_id, seed)
+            .push_section(container::SECTION_DYN_META, meta)
+            .push_section(container::SECTION_DYN_PILOTS, pilots)
+            .push_section(container::SECTION_DYN_REMAP, remap)
+            .push_section(container::SECTION_DYN_KEY_ENDS, ends(self.reorder(keys).map(AsRef::as_ref))?)
+            .push_section(container::SECTION_DYN_VALUE_ENDS, ends(self.reorder(values).map(AsRef::as_ref))?)
+            .push_section(container::SECTION_DYN_KEYS, keys_buf)
+            .push_section(container::SECTION_DYN_VALUES, values_buf)
+            .write(writer)
+    }
+}
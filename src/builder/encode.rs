@@ -0,0 +1,192 @@
+//! Compact encodings of seq
+
+use crate::seq::EF_SPARSE_BLOCK;
+
+/// Span of the ones of a block from which [`EliasFano`] stores their positions
+const EF_MAX_SPAN: usize = 1024;
+
+/// Elias-Fano encoding of monotone seq
+///
+/// See [`EliasFanoSeq`](crate::seq::EliasFanoSeq).
+pub(super) struct EliasFano {
+    pub low_bits: usize,
+    pub samples: Vec<u32>,
+    pub exceptions: Vec<u32>,
+    pub low: Vec<u32>,
+    pub high: Vec<u32>,
+}
+
+impl EliasFano {
+    /// Returns `None` if seq is not monotone,
+    /// or the high bits do not fit in the 31 bits positions of samples.
+    pub fn encode(list: &[u32]) -> Option<EliasFano> {
+        if !list.is_sorted() {
+            return None;
+        }
+
+        let len = list.len() as u64;
+        let max = list.last().copied().map(u64::from).unwrap_or_default();
+        let low_bits = match max.checked_div(len) {
+            Some(n) if n > 0 => n.ilog2() as usize,
+            _ => 0
+        };
+
+        let high_len = (max >> low_bits) + len + 1;
+        if high_len > u64::from(EF_SPARSE_BLOCK) {
+            return None;
+        }
+
+        let mut low = BitWriter::default();
+        let mut high = vec![0u32; high_len.div_ceil(32) as usize];
+        let mut positions = Vec::with_capacity(list.len());
+
+        for (i, &n) in list.iter().enumerate() {
+            low.write(n.into(), low_bits);
+
+            let pos = (n as usize >> low_bits) + i;
+            high[pos / 32] |= 1 << (pos % 32);
+            positions.push(pos as u32);
+        }
+
+        let mut samples = Vec::with_capacity(list.len().div_ceil(64));
+        let mut exceptions = Vec::new();
+
+        for block in positions.chunks(64) {
+            let (first, last) = (block[0], block[block.len() - 1]);
+
+            if ((last - first) as usize) < EF_MAX_SPAN {
+                samples.push(first);
+            } else {
+                samples.push(EF_SPARSE_BLOCK | exceptions.len() as u32);
+                exceptions.extend_from_slice(block);
+            }
+        }
+
+        Some(EliasFano {
+            low_bits, samples, exceptions, high,
+            low: low.finish(),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        (self.samples.len() + self.exceptions.len() + self.low.len() + self.high.len()) * 4
+    }
+}
+
+/// Packs values of fixed bit width into u32 words, in little-endian bit order.
+#[derive(Default)]
+pub(super) struct BitWriter {
+    words: Vec<u32>,
+    bits: usize,
+}
+
+impl BitWriter {
+    pub fn write(&mut self, value: u64, bits: usize) {
+        let mut value = value & mask64(bits);
+        let mut bits = bits;
+
+        while bits > 0 {
+            let offset = self.bits % 32;
+            if offset == 0 {
+                self.words.push(0);
+            }
+
+            let n = bits.min(32 - offset);
+            *self.words.last_mut().unwrap() |= ((value & mask64(n)) << offset) as u32;
+            value >>= n;
+            bits -= n;
+            self.bits += n;
+        }
+    }
+
+    pub fn finish(self) -> Vec<u32> {
+        self.words
+    }
+}
+
+/// Frame-of-reference bit packing
+///
+/// See [`PackedSeq`](crate::seq::PackedSeq).
+pub(super) struct Packed {
+    pub bits: usize,
+    pub base: u64,
+    pub words: Vec<u32>,
+}
+
+impl Packed {
+    pub fn encode(list: &[u64]) -> Packed {
+        let min = list.iter().copied().min().unwrap_or_default();
+        let max = list.iter().copied().max().unwrap_or_default();
+        let width = |n: u64| (u64::BITS - n.leading_zeros()) as usize;
+
+        // Only subtract the base if it saves bits.
+        let base = if width(max - min) < width(max) { min } else { 0 };
+        let bits = width(max - base);
+
+        let mut writer = BitWriter::default();
+        for &n in list {
+            writer.write(n - base, bits);
+        }
+
+        Packed { bits, base, words: writer.finish() }
+    }
+}
+
+/// Escape coded u8 list
+///
+/// See [`EscapedSeq`](crate::seq::EscapedSeq).
+pub(super) struct Escaped {
+    pub bits: usize,
+    pub codes: Vec<u32>,
+    pub samples: Vec<u32>,
+    pub exceptions: Vec<u8>,
+}
+
+impl Escaped {
+    /// Encode with the code width that gives the smallest size.
+    pub fn encode(list: &[u8]) -> Escaped {
+        let mut hist = [0usize; 256];
+        for &n in list {
+            hist[usize::from(n)] += 1;
+        }
+
+        let bits = (1..8)
+            .min_by_key(|&bits| {
+                let escaped: usize = hist[(1 << bits) - 1..].iter().sum();
+                list.len() * bits + escaped * 8
+            })
+            .unwrap();
+        let escape = (1 << bits) - 1;
+
+        let mut writer = BitWriter::default();
+        let mut samples = Vec::with_capacity(list.len().div_ceil(64));
+        let mut exceptions = Vec::new();
+
+        for (i, &n) in list.iter().enumerate() {
+            if i % 64 == 0 {
+                samples.push(exceptions.len() as u32);
+            }
+
+            if n >= escape {
+                writer.write(escape.into(), bits);
+                exceptions.push(n);
+            } else {
+                writer.write(n.into(), bits);
+            }
+        }
+
+        Escaped { bits, codes: writer.finish(), samples, exceptions }
+    }
+
+    pub fn size(&self) -> usize {
+        self.codes.len() * 4 + self.samples.len() * 4 + self.exceptions.len()
+    }
+}
+
+fn mask64(bits: usize) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
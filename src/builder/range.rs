@@ -0,0 +1,117 @@
+use std::ops::{ Bound, RangeBounds };
+use super::BuildFailed;
+
+/// Static Range Map builder
+///
+/// Collects non-overlapping `u32` ranges and their values.
+pub struct RangeMapBuilder<V> {
+    ranges: Vec<(Option<(u32, u32)>, V)>,
+    merge: bool,
+}
+
+/// Range Map output
+#[derive(Debug)]
+pub struct RangeMapOutput<V> {
+    pub(super) starts: Box<[u32]>,
+    pub(super) ends: Box<[u32]>,
+    values: Box<[V]>,
+}
+
+impl<V> Default for RangeMapBuilder<V> {
+    fn default() -> Self {
+        RangeMapBuilder::new()
+    }
+}
+
+impl<V> RangeMapBuilder<V> {
+    pub fn new() -> Self {
+        RangeMapBuilder {
+            ranges: Vec::new(),
+            merge: false,
+        }
+    }
+
+    /// Merge adjacent ranges with equal values
+    pub fn set_merge(&mut self, flag: bool) -> &mut Self {
+        self.merge = flag;
+        self
+    }
+
+    /// Insert a range, such as `lo..hi` or `lo..=hi`.
+    ///
+    /// Empty ranges are rejected by [`RangeMapBuilder::build`].
+    pub fn insert<R: RangeBounds<u32>>(&mut self, range: R, value: V) -> &mut Self {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => Some(n),
+            Bound::Excluded(&n) => n.checked_add(1),
+            Bound::Unbounded => Some(0)
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => Some(n),
+            Bound::Excluded(&n) => n.checked_sub(1),
+            Bound::Unbounded => Some(u32::MAX)
+        };
+        let range = start.zip(end).filter(|(start, end)| start <= end);
+
+        self.ranges.push((range, value));
+        self
+    }
+
+    /// Creates a Range Map with the inserted ranges
+    pub fn build(self) -> Result<RangeMapOutput<V>, BuildFailed>
+    where
+        V: PartialEq
+    {
+        let mut ranges = Vec::with_capacity(self.ranges.len());
+        for (range, value) in self.ranges {
+            let (start, end) = range.ok_or(BuildFailed::EmptyRange)?;
+            ranges.push((start, end, value));
+        }
+        ranges.sort_by_key(|&(start, ..)| start);
+
+        let mut starts = Vec::with_capacity(ranges.len());
+        let mut ends: Vec<u32> = Vec::with_capacity(ranges.len());
+        let mut values: Vec<V> = Vec::with_capacity(ranges.len());
+
+        for (start, end, value) in ranges {
+            if let Some(last) = ends.last_mut() {
+                if start <= *last {
+                    return Err(BuildFailed::OverlappingRanges);
+                }
+
+                if self.merge
+                    && start - 1 == *last
+                    && values.last() == Some(&value)
+                {
+                    *last = end;
+                    continue
+                }
+            }
+
+            starts.push(start);
+            ends.push(end);
+            values.push(value);
+        }
+
+        Ok(RangeMapOutput {
+            starts: starts.into_boxed_slice(),
+            ends: ends.into_boxed_slice(),
+            values: values.into_boxed_slice(),
+        })
+    }
+}
+
+impl<V> RangeMapOutput<V> {
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// The values in range order, used to create the values seq of map.
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+}
@@ -2,7 +2,203 @@ use std::fmt::Write;
 use std::hash::{ Hash, Hasher };
 use std::collections::hash_map::DefaultHasher;
 use std::time::Instant;
-use super::{ MapBuilder, MapKind };
+use std::ops::Bound;
+use crate::phf;
+use super::{ build, MapBuilder, BuildFailed, HashFunc, MapKind, Pilots, RangeMapBuilder, Fingerprint, Fingerprints, ContainerWriter };
+
+/// Static seq over a list computed at runtime
+///
+/// `test_seq!(Name: [T; LEN])` defines `Name` as `AccessSeq<Item = T>`,
+/// the list is set once by `Name::set`. Without `LEN`, the length is unbounded.
+/// `test_seq!(Name: bytes [N])` defines `Name` as `AsData<Data = [u8; N]>`.
+macro_rules! test_seq {
+    ( $name:ident: [$item:ty] ) => {
+        test_seq!($name: [$item; usize::MAX]);
+    };
+    ( $name:ident: [$item:ty; $len:expr] ) => {
+        struct $name;
+
+        impl $name {
+            fn list() -> &'static std::sync::OnceLock<Vec<$item>> {
+                static LIST: std::sync::OnceLock<Vec<$item>> = std::sync::OnceLock::new();
+                &LIST
+            }
+
+            #[allow(dead_code)]
+            fn set(list: impl IntoIterator<Item = $item>) {
+                let list = list.into_iter().collect::<Vec<$item>>();
+                assert!($len == usize::MAX || list.len() == $len, "{}: {}", stringify!($name), list.len());
+                $name::list().set(list).unwrap();
+            }
+        }
+
+        impl crate::store::AccessSeq for $name {
+            type Item = $item;
+            const LEN: usize = $len;
+
+            fn index(index: usize) -> Option<$item> {
+                $name::list().get()?.get(index).copied()
+            }
+        }
+    };
+    ( $name:ident: bytes [$len:expr] ) => {
+        struct $name;
+
+        impl $name {
+            fn list() -> &'static std::sync::OnceLock<Vec<u8>> {
+                static LIST: std::sync::OnceLock<Vec<u8>> = std::sync::OnceLock::new();
+                &LIST
+            }
+
+            fn set(list: Vec<u8>) {
+                $name::list().set(list).unwrap();
+            }
+        }
+
+        impl crate::store::AsData for $name {
+            type Data = [u8; $len];
+
+            fn as_data() -> &'static [u8; $len] {
+                $name::list().get().unwrap()[..].try_into().unwrap()
+            }
+        }
+    };
+}
+
+/// Output of [`generate`]
+struct Generated {
+    code: String,
+    hash_id: u32,
+    seed: u64,
+    u8seq: Vec<u8>,
+    u32seq: Vec<u8>,
+    manifest: String,
+}
+
+/// Run `CodeBuilder` in a temporary directory,
+/// the blobs and manifest are read back from `write_container`.
+fn generate(name: &str, f: impl FnOnce(&mut super::CodeBuilder<'_>)) -> Generated {
+    use super::{ CodeBuilder, U8SeqWriter, U32SeqWriter };
+    use crate::container::{ self, Container };
+
+    let dir = std::env::temp_dir().join(format!("precomputed-map-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut u8seq = U8SeqWriter::new("U8".into(), dir.join("u8seq"));
+    let mut u32seq = U32SeqWriter::new("U32".into(), dir.join("u32seq"));
+    let mut builder = CodeBuilder::new("Test".into(), "Hash".into(), &mut u8seq, &mut u32seq);
+    f(&mut builder);
+
+    let mut buf = Vec::new();
+    builder.write_container(7, &mut buf).unwrap();
+    let mut code = Vec::new();
+    builder.codegen(&mut code).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let c = Container::parse(&buf).unwrap();
+    let section = |tag| c.section(tag).unwrap().to_vec();
+    Generated {
+        code: String::from_utf8(code).unwrap(),
+        hash_id: c.hash_id(),
+        seed: c.seed(),
+        u8seq: section(container::SECTION_U8SEQ),
+        u32seq: section(container::SECTION_U32SEQ),
+        manifest: String::from_utf8(section(container::SECTION_MANIFEST)).unwrap(),
+    }
+}
+
+/// xorshift64, for test data
+fn xorshift(mut state: u64) -> impl FnMut() -> u64 {
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+}
+
+/// Keys and output of a map shared by the query tests
+struct Fixture {
+    keys: Vec<u64>,
+    output: super::MapOutput,
+    seed: u64,
+}
+
+// Medium map of u64 keys with the default params, alpha 0.99 and lambda 3.0,
+// the lengths are the same formulas as the builder.
+type MediumHash = phf::U64Hasher<DefaultHasher>;
+const MEDIUM_LEN: usize = 1000;
+const MEDIUM_SLOTS: usize = {
+    let len = (MEDIUM_LEN * 100).div_ceil(99);
+    len + len.is_power_of_two() as usize
+};
+test_seq!(MediumPilots: [u16; MEDIUM_LEN.div_ceil(3) + 3]);
+test_seq!(MediumRemap: [u32; MEDIUM_SLOTS - MEDIUM_LEN]);
+test_seq!(MediumKeys: [u64; MEDIUM_LEN]);
+
+/// Build the medium map once and set its stores
+fn medium_fixture() -> &'static Fixture {
+    use phf::HashOne;
+
+    static FIXTURE: std::sync::OnceLock<Fixture> = std::sync::OnceLock::new();
+
+    FIXTURE.get_or_init(|| {
+        let keys = (0..MEDIUM_LEN as u64).map(|k| k * 3).collect::<Vec<u64>>();
+        let output = MapBuilder::new()
+            .set_seed(42)
+            .set_hash(&|seed, k| MediumHash::hash_one(seed, k))
+            .build(&keys)
+            .unwrap();
+        let MapKind::Medium { seed, pilots, remap } = &output.kind else {
+            panic!("{:?}", output.kind)
+        };
+        MediumPilots::set(pilot_list(pilots));
+        MediumRemap::set(remap.iter().copied());
+        MediumKeys::set(output.reorder(&keys).copied());
+
+        Fixture { seed: *seed, keys, output }
+    })
+}
+
+// Large map of u64 keys with the default params and the `Mix` hash.
+//
+// The buckets of each shard are sized by the average shard length,
+// the remap depends on the length of each shard, but `LargeMap` does not use its length.
+const LARGE_LEN: usize = 4000;
+const LARGE_SHARD_KEYS: usize = 1024;
+const LARGE_SHARDS: usize = LARGE_LEN.div_ceil(LARGE_SHARD_KEYS);
+test_seq!(KeyOffsets: [u32; LARGE_SHARDS + 1]);
+test_seq!(SlotOffsets: [u32; LARGE_SHARDS + 1]);
+test_seq!(ShardRetries: [u32; LARGE_SHARDS]);
+test_seq!(LargePilots: [u16; LARGE_SHARDS * (LARGE_LEN.div_ceil(LARGE_SHARDS).div_ceil(3) + 3)]);
+test_seq!(LargeRemap: [u32]);
+test_seq!(LargeKeys: [u64; LARGE_LEN]);
+
+/// Build the large map once and set its stores
+fn large_fixture() -> &'static Fixture {
+    use phf::{ HashOne, Mix };
+
+    static FIXTURE: std::sync::OnceLock<Fixture> = std::sync::OnceLock::new();
+
+    FIXTURE.get_or_init(|| {
+        let keys = (0..LARGE_LEN as u64).collect::<Vec<u64>>();
+        let mut builder = MapBuilder::new();
+        builder.set_seed(42).set_hash(&|seed, k| Mix::hash_one(seed, k));
+        let output = build::build_large(&builder, &keys, LARGE_SHARD_KEYS).unwrap();
+        let MapKind::Large { seed, key_offsets, slot_offsets, shard_retries, pilots, remap } = &output.kind else {
+            panic!("{:?}", output.kind)
+        };
+        KeyOffsets::set(key_offsets.iter().copied());
+        SlotOffsets::set(slot_offsets.iter().copied());
+        ShardRetries::set(shard_retries.iter().copied());
+        LargePilots::set(pilot_list(pilots));
+        LargeRemap::set(remap.iter().copied());
+        LargeKeys::set(output.reorder(&keys).copied());
+
+        Fixture { seed: *seed, keys, output }
+    })
+}
 
 
 #[test]
@@ -48,3 +244,1486 @@ fn test_build_ptrhash() {
         assert_eq!(3559301822128966697, *seed);
     }
 }
+
+#[test]
+fn test_build_large() {
+    use phf::{ HashOne, Mix };
+    use crate::LargeMap;
+    use super::{ PhfParams, PilotWidth };
+
+    const KEYS_LEN: usize = 64 * 1024;
+    const SHARDS_LEN: usize = 64;
+    // buckets of each shard, with lambda 3.0
+    const PILOTS_LEN: usize = SHARDS_LEN * ((KEYS_LEN / SHARDS_LEN).div_ceil(3) + 3);
+
+    test_seq!(KeyOffsets: [u32; SHARDS_LEN + 1]);
+    test_seq!(SlotOffsets: [u32; SHARDS_LEN + 1]);
+    test_seq!(ShardRetries: [u32; SHARDS_LEN]);
+    test_seq!(PilotSeq: [u16; PILOTS_LEN]);
+    test_seq!(RemapSeq: [u32; 0]);
+    test_seq!(KeySeq: [u64; KEYS_LEN]);
+
+    // dense enough that some shards need another pilot seed
+    let keys = (0..KEYS_LEN as u64).collect::<Vec<u64>>();
+    let mut builder = MapBuilder::<u64>::new();
+    builder.set_seed(42)
+        .set_limit(Some(0))
+        .set_hash(&|seed, k| Mix::hash_one(seed, k))
+        .set_params(PhfParams { alpha: 1.0, lambda: 3.0 })
+        .set_pilot_width(Some(PilotWidth::U8));
+    let output = build::build_large(&builder, &keys, 1024).unwrap();
+
+    let MapKind::Large { seed, key_offsets, slot_offsets, shard_retries, pilots, remap } = &output.kind else {
+        panic!("{:?}", output.kind)
+    };
+    assert_eq!(*seed, 42);
+    assert!(shard_retries.iter().any(|&retry| retry > 0), "{:?}", shard_retries);
+    KeyOffsets::set(key_offsets.iter().copied());
+    SlotOffsets::set(slot_offsets.iter().copied());
+    ShardRetries::set(shard_retries.iter().copied());
+    PilotSeq::set(pilot_list(pilots));
+    RemapSeq::set(remap.iter().copied());
+    KeySeq::set(output.reorder(&keys).copied());
+
+    let map = LargeMap::<KeyOffsets, SlotOffsets, ShardRetries, PilotSeq, RemapSeq, KeySeq, Mix>::new(*seed);
+    for (pos, &idx) in output.index.iter().enumerate() {
+        assert_eq!(map.get(&keys[idx]), Some(pos));
+        assert_eq!(map.index_of(&keys[idx]), Some(pos));
+    }
+    for key in KEYS_LEN as u64..KEYS_LEN as u64 + 1000 {
+        assert_eq!(map.get(&key), None);
+    }
+    assert_eq!(map.len(), KEYS_LEN);
+    assert_eq!(map.iter().len(), KEYS_LEN);
+}
+
+fn pilot_list(pilots: &Pilots) -> Vec<u16> {
+    match pilots {
+        Pilots::U8(pilots) => pilots.iter().copied().map(u16::from).collect(),
+        Pilots::U16(pilots) => pilots.to_vec()
+    }
+}
+
+#[test]
+fn test_build_params() {
+    use phf::{ HashOne, U64Hasher };
+    use crate::MediumMap;
+    use crate::store::AccessSeq;
+    use super::{ PhfParams, PilotWidth };
+
+    type Hash = U64Hasher<DefaultHasher>;
+
+    const KEYS_LEN: usize = 50 * 1024;
+
+    let keys = (0..KEYS_LEN as u64).collect::<Vec<u64>>();
+
+    /// Build a medium map with params, and query it by `MediumMap`
+    ///
+    /// The lengths of pilots and remap only depend on params and number of keys.
+    macro_rules! check {
+        ($params:expr, $width:expr, [$pilots_len:expr, $remap_len:expr]) => {{
+            test_seq!(PilotSeq: [u16; $pilots_len]);
+            test_seq!(RemapSeq: [u32; $remap_len]);
+            test_seq!(KeySeq: [u64; KEYS_LEN]);
+
+            let mut builder = MapBuilder::<u64>::new();
+            builder.set_seed(42)
+                .set_limit(Some(0))
+                .set_hash(&|seed, k| Hash::hash_one(seed, k))
+                .set_params($params)
+                .set_pilot_width(Some($width));
+            let output = build::build_medium(&builder, &keys).unwrap();
+
+            let MapKind::Medium { seed, pilots, remap } = &output.kind else {
+                panic!("{:?}", output.kind)
+            };
+            assert_eq!(matches!(pilots, Pilots::U16(_)), $width == PilotWidth::U16);
+            PilotSeq::set(pilot_list(pilots));
+            RemapSeq::set(remap.iter().copied());
+            KeySeq::set(output.reorder(&keys).copied());
+
+            let map = MediumMap::<PilotSeq, RemapSeq, KeySeq, Hash>::new(*seed);
+            for (pos, key) in output.reorder(&keys).enumerate() {
+                assert_eq!(map.get(key), Some(pos));
+            }
+            for key in KEYS_LEN as u64..KEYS_LEN as u64 + 1000 {
+                assert_eq!(map.get(&key), None);
+            }
+
+            RemapSeq::LEN
+        }};
+    }
+
+    check!(PhfParams::DEFAULT, PilotWidth::U8, [17070, 518]);
+    check!(PhfParams::fastest_build(), PilotWidth::U8, [20483, 1045]);
+    check!(PhfParams::smallest(), PilotWidth::U8, [15062, 518]);
+
+    // no spare slots, so no remap
+    let remap_len = check!(PhfParams::fastest_query(), PilotWidth::U8, [20483, 0]);
+    assert_eq!(remap_len, 0);
+    // too dense for u8 pilots
+    let remap_len = check!(PhfParams { alpha: 1.0, lambda: 5.0 }, PilotWidth::U16, [10243, 0]);
+    assert_eq!(remap_len, 0);
+
+    let mut builder = MapBuilder::<u64>::new();
+    builder.set_hash(&|seed, k| Hash::hash_one(seed, k)).set_params(PhfParams { alpha: 1.5, lambda: 3.0 });
+    assert_eq!(build::build_medium(&builder, &keys).unwrap_err(), BuildFailed::InvalidParams);
+}
+
+#[test]
+fn test_build_deterministic() {
+    // SplitMix64 reference outputs of seed 0
+    assert_eq!(super::next_seed(0, 0), 0xe220a8397b1dcdaf);
+    assert_eq!(super::next_seed(0, 1), 0x6e789e6aa1b965f4);
+    assert_eq!(super::next_seed(0, 2), 0x06c45d188009454f);
+
+    let hash = |key, v: &u64| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(key);
+        v.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    for len in [10, 10 * 1024] {
+        let keys = (0..len).collect::<Vec<u64>>();
+
+        let output = MapBuilder::<u64>::deterministic().set_hash(&hash).build(&keys).unwrap();
+        let output2 = MapBuilder::<u64>::deterministic().set_hash(&hash).set_threads(4).build(&keys).unwrap();
+
+        assert_eq!(output.index, output2.index);
+        assert_eq!(format!("{:?}", output.kind), format!("{:?}", output2.kind));
+
+        let seed = output.seed().unwrap();
+        assert!(seed == super::GOLDEN || (0..128 * 1024).any(|c| seed == super::next_seed(super::GOLDEN, c)));
+
+        // `new` is deterministic too, random seed is opt-in
+        let output3 = MapBuilder::<u64>::new().set_hash(&hash).build(&keys).unwrap();
+        assert_eq!(output.index, output3.index);
+        assert_eq!(output3.seed(), Some(seed));
+    }
+
+    let keys = (0..10 * 1024).collect::<Vec<u64>>();
+    let random = || MapBuilder::<u64>::new()
+        .set_random_seed(true)
+        .set_hash(&hash)
+        .build(&keys)
+        .unwrap()
+        .seed();
+    assert_ne!(random(), random());
+}
+
+#[test]
+fn test_build_cached() {
+    use super::SeedCache;
+
+    let path = std::env::temp_dir().join(format!("precomputed-map-seed-cache-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let hash = |key, v: &u64| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(key);
+        v.hash(&mut hasher);
+        hasher.finish()
+    };
+    let mut keys = (0..10 * 1024).collect::<Vec<u64>>();
+
+    let mut cache = SeedCache::open(path.clone()).unwrap();
+    let output = MapBuilder::<u64>::new().set_random_seed(true).set_hash(&hash).build_cached("a", &keys, &mut cache).unwrap();
+    cache.save().unwrap();
+
+    // the random seed is replaced by the cached seed
+    let mut cache = SeedCache::open(path.clone()).unwrap();
+    let output2 = MapBuilder::<u64>::new().set_random_seed(true).set_hash(&hash).build_cached("a", &keys, &mut cache).unwrap();
+    assert_eq!(output.seed(), output2.seed());
+    assert_eq!(output.index, output2.index);
+
+    // the key order doesn't matter, but the key set does
+    keys.reverse();
+    let output3 = MapBuilder::<u64>::new().set_random_seed(true).set_hash(&hash).build_cached("a", &keys, &mut cache).unwrap();
+    assert_eq!(output.seed(), output3.seed());
+    keys.push(10 * 1024);
+    let output4 = MapBuilder::<u64>::new().set_random_seed(true).set_hash(&hash).build_cached("a", &keys, &mut cache).unwrap();
+    assert_ne!(output.seed(), output4.seed());
+    cache.save().unwrap();
+
+    let mut cache = SeedCache::open(path.clone()).unwrap();
+    let fingerprint = build::key_fingerprint(MapBuilder::<u64>::new().set_hash(&hash), &keys).unwrap();
+    assert_eq!(cache.get("a", fingerprint), output4.seed());
+    assert_eq!(cache.get("a", fingerprint ^ 1), None);
+    assert_eq!(cache.get("b", fingerprint), None);
+
+    // the options that change the output are mixed into the fingerprint
+    {
+        use super::{ PhfParams, PilotWidth };
+
+        let fingerprints = [
+            build::key_fingerprint(MapBuilder::new().set_hash(&hash).set_params(PhfParams::fastest_build()), &keys),
+            build::key_fingerprint(MapBuilder::new().set_hash(&hash).set_pilot_width(Some(PilotWidth::U16)), &keys),
+            build::key_fingerprint(MapBuilder::new().set_hash(&hash).set_fingerprint(Some(Fingerprint::U8)), &keys),
+            build::key_fingerprint(MapBuilder::new().set_hash(&hash).force_build(true), &keys),
+        ];
+        for other in fingerprints {
+            assert_ne!(other, Some(fingerprint));
+        }
+    }
+
+    // the wide hashes of fingerprint are reused by build
+    {
+        use phf::{ HashWide, Wide, U64Hasher };
+
+        let calls = std::cell::Cell::new(0);
+        let hash_wide = |k: &u64| {
+            calls.set(calls.get() + 1);
+            Wide::<U64Hasher<DefaultHasher>>::hash_wide(k)
+        };
+        MapBuilder::new().set_hash_wide(&hash_wide).build_cached("w", &keys, &mut cache).unwrap();
+        assert_eq!(calls.get(), keys.len());
+    }
+
+    // the fingerprint is of normalized keys
+    let keys = (1000..1100).collect::<Vec<u64>>();
+    let output5 = MapBuilder::<u64>::new()
+        .set_hash(&hash)
+        .set_normalize(&|k| k % 1000)
+        .build_cached("n", &keys, &mut cache)
+        .unwrap();
+    let normalized = (0..100).collect::<Vec<u64>>();
+    let fingerprint = build::key_fingerprint(MapBuilder::<u64>::new().set_hash(&hash), &normalized).unwrap();
+    assert_eq!(cache.get("n", fingerprint), output5.seed());
+
+    std::fs::write(&path, "a 12 zz\n").unwrap();
+    assert!(SeedCache::open(path.clone()).is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_build_threads() {
+    let keys = (0..200 * 1024).collect::<Vec<u64>>();
+    let hash = |key, v: &u64| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(key);
+        v.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let mut builder = MapBuilder::<u64>::new();
+    builder.set_seed(42).set_hash(&hash);
+    let medium = build::build_medium(&builder, &keys).unwrap();
+    let large = build::build_large(&builder, &keys, 64 * 1024).unwrap();
+
+    builder.set_threads(4);
+    let medium2 = build::build_medium(&builder, &keys).unwrap();
+    let large2 = build::build_large(&builder, &keys, 64 * 1024).unwrap();
+
+    // keys are hashed in parallel too
+    builder.set_hash_sync(&hash);
+    let medium3 = build::build_medium(&builder, &keys).unwrap();
+    let large3 = build::build_large(&builder, &keys, 64 * 1024).unwrap();
+
+    for (medium2, large2) in [(medium2, large2), (medium3, large3)] {
+        assert_eq!(medium.index, medium2.index);
+        assert_eq!(format!("{:?}", medium.kind), format!("{:?}", medium2.kind));
+        assert_eq!(large.index, large2.index);
+        assert_eq!(format!("{:?}", large.kind), format!("{:?}", large2.kind));
+    }
+}
+
+#[test]
+fn test_build_threads_output() {
+    use phf::{ HashOne, HashWide, Mix, Wide, U64Hasher };
+
+    let keys = (0..200 * 1024).collect::<Vec<u64>>();
+    let hash = |seed, v: &u64| Mix::hash_one(seed, v);
+    let hash_wide = |v: &u64| Wide::<U64Hasher<DefaultHasher>>::hash_wide(v);
+
+    let render = |name: &str, output: &super::MapOutput| {
+        let generated = generate(name, |builder| {
+            let k = builder.create_packed_u64_seq("Keys".into(), output.reorder(&keys).copied()).unwrap();
+            output.create_set("SET".into(), k, builder).unwrap();
+        });
+        (generated.code, generated.u8seq, generated.u32seq, generated.manifest)
+    };
+
+    for wide in [false, true] {
+        let mut outputs = Vec::new();
+
+        for threads in [1, 4] {
+            let mut builder = MapBuilder::<u64>::new();
+            builder.set_seed(42).set_threads(threads);
+            match wide {
+                false => builder.set_hash_sync(&hash),
+                true => builder.set_hash_wide_sync(&hash_wide)
+            };
+
+            let medium = build::build_medium(&builder, &keys).unwrap();
+            let large = build::build_large(&builder, &keys, 64 * 1024).unwrap();
+            outputs.push((
+                render(&format!("threads-medium-{}-{}", wide, threads), &medium),
+                render(&format!("threads-large-{}-{}", wide, threads), &large)
+            ));
+        }
+
+        assert!(outputs[0] == outputs[1], "wide: {}", wide);
+    }
+}
+
+#[test]
+fn test_build_hash_wide() {
+    use phf::{ HashOne, HashWide, Remix, Wide, U64Hasher };
+
+    type Hash = Remix<Wide<U64Hasher<DefaultHasher>>>;
+
+    let keys = (0..10 * 1024).map(|n| format!("{}", n)).collect::<Vec<_>>();
+
+    let mut builder = MapBuilder::<String>::new();
+    builder.set_seed(42).set_hash_wide(&|v| Wide::<U64Hasher<DefaultHasher>>::hash_wide(v));
+    let output = builder.build(&keys).unwrap();
+
+    // the runtime hash derives the same seeded hash
+    let mut builder = MapBuilder::<String>::new();
+    builder.set_seed(42).set_hash(&|seed, v| Hash::hash_one(seed, v));
+    let output2 = builder.build(&keys).unwrap();
+
+    assert_eq!(output.index, output2.index);
+    assert_eq!(format!("{:?}", output.kind), format!("{:?}", output2.kind));
+}
+
+#[test]
+fn test_tiny_range() {
+    crate::define!(const searchable Keys: &[u32; 5] = &[1, 3, 5, 7, 9]);
+    crate::define!(const Values: &[u8; 5] = &[10, 30, 50, 70, 90]);
+
+    let map = crate::TinyMap::<(Keys, Values)>::new();
+
+    assert_eq!(map.first(), Some((1, 10)));
+    assert_eq!(map.last(), Some((9, 90)));
+    assert_eq!(map.lower_bound(&5), Some((5, 50)));
+    assert_eq!(map.lower_bound(&6), Some((7, 70)));
+    assert_eq!(map.upper_bound(&5), Some((7, 70)));
+    assert_eq!(map.upper_bound(&9), None);
+    assert_eq!(map.range(..=6).next_back(), Some((5, 50)));
+    assert_eq!(map.range(..=0).next_back(), None);
+    assert_eq!(map.range(3..7).collect::<Vec<_>>(), vec![(3, 30), (5, 50)]);
+    assert_eq!(map.range(4..=9).rev().collect::<Vec<_>>(), vec![(9, 90), (7, 70), (5, 50)]);
+    assert_eq!(map.range((Bound::Included(7), Bound::Excluded(3))).len(), 0);
+    assert_eq!(map.range::<u32, _>(..).len(), 5);
+}
+
+#[test]
+fn test_build_ordered() {
+    let keys = [9u32, 1, 13, 5, 3, 11, 7];
+    let mapout = MapBuilder::new()
+        .set_ord(&|x: &u32, y: &u32| x.cmp(y))
+        .build_ordered(&keys)
+        .unwrap();
+    let layout = mapout.index.iter().map(|&idx| keys[idx]).collect::<Vec<_>>();
+    assert_eq!(layout, vec![7, 3, 11, 1, 5, 9, 13]);
+
+    assert!(MapBuilder::<u32>::new().build_ordered(&keys).is_err());
+
+    crate::define!(const Keys: &[u32; 7] = &[7, 3, 11, 1, 5, 9, 13]);
+    crate::define!(const Values: &[u8; 7] = &[70, 30, 110, 10, 50, 90, 130]);
+
+    let map = crate::OrderedMap::<(Keys, Values)>::new();
+
+    assert_eq!(map.get(&5), Some(50));
+    assert_eq!(map.get(&6), None);
+    assert_eq!(map.first(), Some((1, 10)));
+    assert_eq!(map.last(), Some((13, 130)));
+    assert_eq!(map.lower_bound(&6), Some((7, 70)));
+    assert_eq!(map.upper_bound(&13), None);
+    assert_eq!(map.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec![1, 3, 5, 7, 9, 11, 13]);
+    assert_eq!(map.range(4..=11).rev().collect::<Vec<_>>(), vec![(11, 110), (9, 90), (7, 70), (5, 50)]);
+    assert_eq!(map.range(2..3).len(), 0);
+    assert_eq!(map.range((Bound::Included(9), Bound::Excluded(3))).len(), 0);
+
+    let set = crate::OrderedSet::<Keys>::new();
+    assert!(set.contains(&11));
+    assert!(!set.contains(&12));
+    assert_eq!(set.range(3..9).collect::<Vec<_>>(), vec![3, 5, 7]);
+}
+
+#[test]
+fn test_build_range() {
+    let mut builder = RangeMapBuilder::new();
+    builder
+        .set_merge(true)
+        .insert(20..30, 'b')
+        .insert(0..10, 'a')
+        .insert(10..=19, 'a')
+        .insert(30..40, 'c')
+        .insert(100.., 'd');
+    let output = builder.build().unwrap();
+    assert_eq!(&*output.starts, &[0, 20, 30, 100]);
+    assert_eq!(&*output.ends, &[19, 29, 39, u32::MAX]);
+    assert_eq!(output.values(), &['a', 'b', 'c', 'd']);
+
+    let mut builder = RangeMapBuilder::new();
+    builder.insert(0..10, 'a').insert(9..20, 'b');
+    assert!(matches!(builder.build(), Err(BuildFailed::OverlappingRanges)));
+
+    let mut builder = RangeMapBuilder::new();
+    builder.insert(10..10, 'a');
+    assert!(matches!(builder.build(), Err(BuildFailed::EmptyRange)));
+
+    crate::define!(const Starts: &[u32; 4] = &[0, 20, 30, 100]);
+    crate::define!(const Ends: &[u32; 4] = &[19, 29, 39, u32::MAX]);
+    crate::define!(const Values: &[char; 4] = &['a', 'b', 'c', 'd']);
+
+    let map = crate::RangeMap::<Starts, Ends, Values>::new();
+
+    assert_eq!(map.get(0), Some('a'));
+    assert_eq!(map.get(19), Some('a'));
+    assert_eq!(map.get(20), Some('b'));
+    assert_eq!(map.get(39), Some('c'));
+    assert_eq!(map.get(40), None);
+    assert_eq!(map.get(99), None);
+    assert_eq!(map.get(u32::MAX), Some('d'));
+    assert_eq!(map.get_range_value(25), Some((20..=29, 'b')));
+    assert_eq!(map.iter().len(), 4);
+}
+
+#[test]
+fn test_build_fingerprint() {
+    use crate::store::{ self, MapStore };
+
+    let hash = |seed: u64, key: &u64| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(seed);
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let keys = (0..5000u64).collect::<Vec<_>>();
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&hash)
+        .set_fingerprint(Some(Fingerprint::U16))
+        .build(&keys)
+        .unwrap();
+    let seed = output.seed().unwrap();
+
+    let Some(Fingerprints::U16(fingerprints)) = &output.fingerprints else {
+        panic!()
+    };
+    assert_eq!(fingerprints.len(), keys.len());
+    for (&idx, &fp) in output.index.iter().zip(fingerprints.iter()) {
+        assert_eq!(fp, <u16 as store::Fingerprint>::from_hash(hash(seed, &keys[idx])));
+    }
+
+    let output = MapBuilder::new()
+        .set_hash(&hash)
+        .build(&keys)
+        .unwrap();
+    assert!(output.fingerprints.is_none());
+
+    crate::define!(const Keys: &[u32; 2] = &[1, 2]);
+    crate::define!(const Fps: &[u8; 2] = &[0x34, 0x78]);
+
+    type Data = store::WithFingerprint<Fps, Keys>;
+    assert!(Data::check_fingerprint(0, 0x0000_0000_0000_0034));
+    assert!(Data::check_fingerprint(1, 0x0000_0078_0000_0000));
+    assert!(!Data::check_fingerprint(1, 0x0000_0034_0000_0000));
+    assert!(!Data::check_fingerprint(2, 0));
+}
+
+#[test]
+fn test_keyless_index_of() {
+    use phf::{ HashOne, U64Hasher, Mix };
+
+    type Hash = U64Hasher<DefaultHasher>;
+
+    let keys = [3u64, 14, 15, 92, 65, 35, 89, 79];
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&|seed, k| Hash::hash_one(seed, k))
+        .build(&keys)
+        .unwrap();
+    assert!(matches!(output.kind, MapKind::Small(_)));
+
+    let map = crate::SmallMap::<crate::store::Keyless<8>, Hash>::new(output.seed().unwrap());
+    for (pos, &idx) in output.index.iter().enumerate() {
+        assert_eq!(map.index_of(&keys[idx]), Some(pos));
+    }
+    assert_eq!(map.iter().len(), 8);
+
+    // medium
+    let Fixture { keys, output, seed } = medium_fixture();
+    let map = crate::MediumMap::<
+        MediumPilots, MediumRemap, crate::store::Keyless<MEDIUM_LEN>, MediumHash
+    >::new(*seed);
+    for (pos, &idx) in output.index.iter().enumerate() {
+        assert_eq!(map.index_of(&keys[idx]), Some(pos));
+    }
+    assert_eq!(map.iter().len(), MEDIUM_LEN);
+
+    // large
+    let Fixture { keys, output, seed } = large_fixture();
+    let map = crate::LargeMap::<
+        KeyOffsets, SlotOffsets, ShardRetries, LargePilots, LargeRemap,
+        crate::store::Keyless<LARGE_LEN>, Mix
+    >::new(*seed);
+    for (pos, &idx) in output.index.iter().enumerate() {
+        assert_eq!(map.index_of(&keys[idx]), Some(pos));
+    }
+    assert_eq!(map.iter().len(), LARGE_LEN);
+}
+
+#[test]
+fn test_get_batch() {
+    use phf::{ HashOne, U64Hasher, Mix };
+
+    type Hash = U64Hasher<DefaultHasher>;
+
+    /// Compare `get_batch` and `get_many` with `get`, for batches of any length
+    fn check<Q: Copy + Eq + std::fmt::Debug>(
+        query: &[u64],
+        get: impl Fn(&u64) -> Option<Q>,
+        get_batch: impl Fn(&[&u64], &mut [Option<Q>]),
+        get_many: impl Fn([&u64; 5]) -> [Option<Q>; 5]
+    ) {
+        let expected = query.iter().map(&get).collect::<Vec<_>>();
+        assert!(expected.iter().any(Option::is_some));
+        assert!(expected.iter().any(Option::is_none));
+
+        // not multiple of 16, so the tail is not a full batch
+        for len in [0, 1, 5, 15, 17, 33, 100, query.len()] {
+            let query = query[..len].iter().collect::<Vec<_>>();
+            let mut output = vec![None; len];
+            get_batch(&query, &mut output);
+            assert_eq!(output, expected[..len]);
+        }
+
+        let query = [&query[0], &query[1], &query[2], &query[3], &query[4]];
+        assert_eq!(get_many(query), query.map(&get));
+    }
+
+    // hits and misses are interleaved
+    let mut next = xorshift(0x9e37_79b9);
+
+    // small
+    test_seq!(SmallKeys: [u64; 8]);
+
+    let keys = [3u64, 14, 15, 92, 65, 35, 89, 79];
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&|seed, k| Hash::hash_one(seed, k))
+        .build(&keys)
+        .unwrap();
+    SmallKeys::set(output.reorder(&keys).copied());
+
+    let map = crate::SmallMap::<SmallKeys, Hash>::new(output.seed().unwrap());
+    let query = (0..300).map(|_| next() % 128).collect::<Vec<u64>>();
+    check(&query, |k| map.get(k), |q, o| map.get_batch(q, o), |q| map.get_many(q));
+
+    // medium
+    let seed = medium_fixture().seed;
+    let map = crate::MediumMap::<MediumPilots, MediumRemap, MediumKeys, MediumHash>::new(seed);
+    let query = (0..300).map(|_| next() % (3 * MEDIUM_LEN as u64)).collect::<Vec<u64>>();
+    check(&query, |k| map.get(k), |q, o| map.get_batch(q, o), |q| map.get_many(q));
+
+    // large
+    let seed = large_fixture().seed;
+    let map = crate::LargeMap::<
+        KeyOffsets, SlotOffsets, ShardRetries, LargePilots, LargeRemap, LargeKeys, Mix
+    >::new(seed);
+    let query = (0..300).map(|_| next() % (2 * LARGE_LEN as u64)).collect::<Vec<u64>>();
+    check(&query, |k| map.get(k), |q, o| map.get_batch(q, o), |q| map.get_many(q));
+}
+
+#[test]
+fn test_sets() {
+    use phf::{ HashOne, U64Hasher, Mix };
+
+    type Hash = U64Hasher<DefaultHasher>;
+
+    /// Check `contains`, `len` and `iter` with the keys and some misses
+    fn check(
+        keys: &[u64],
+        misses: impl Iterator<Item = u64>,
+        contains: impl Fn(&u64) -> bool,
+        len: usize,
+        iter: impl Iterator<Item = u64>
+    ) {
+        for key in keys {
+            assert!(contains(key), "{}", key);
+        }
+        for key in misses {
+            assert!(!contains(&key), "{}", key);
+        }
+        assert_eq!(len, keys.len());
+
+        let mut list = iter.collect::<Vec<_>>();
+        list.sort_unstable();
+        let mut keys = keys.to_vec();
+        keys.sort_unstable();
+        assert_eq!(list, keys);
+    }
+
+    // tiny
+    crate::define!(const searchable TinyKeys: &[u64; 5] = &[1, 3, 5, 7, 9]);
+
+    let set = crate::TinySet::<TinyKeys>::new();
+    check(&[1, 3, 5, 7, 9], [0, 2, 8, 10].into_iter(), |k| set.contains(k), set.len(), set.iter());
+
+    // small
+    test_seq!(SmallKeys: [u64; 8]);
+
+    let keys = [3u64, 14, 15, 92, 65, 35, 89, 79];
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&|seed, k| Hash::hash_one(seed, k))
+        .build(&keys)
+        .unwrap();
+    SmallKeys::set(output.reorder(&keys).copied());
+
+    let set = crate::SmallSet::<SmallKeys, Hash>::new(output.seed().unwrap());
+    let misses = (0..128).filter(|k| !keys.contains(k));
+    check(&keys, misses, |k| set.contains(k), set.len(), set.iter());
+
+    // medium
+    let Fixture { keys, seed, .. } = medium_fixture();
+    let set = crate::MediumSet::<MediumPilots, MediumRemap, MediumKeys, MediumHash>::new(*seed);
+    let misses = (0..3 * MEDIUM_LEN as u64).filter(|k| k % 3 != 0);
+    check(keys, misses, |k| set.contains(k), set.len(), set.iter());
+
+    // large
+    let Fixture { keys, seed, .. } = large_fixture();
+    let set = crate::LargeSet::<
+        KeyOffsets, SlotOffsets, ShardRetries, LargePilots, LargeRemap, LargeKeys, Mix
+    >::new(*seed);
+    check(keys, LARGE_LEN as u64..2 * LARGE_LEN as u64, |k| set.contains(k), set.len(), set.iter());
+
+    // generated by `MapOutput::create_set`
+    let keys = (0..100).map(|k| k * 7).collect::<Vec<u32>>();
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&|seed, k| Hash::hash_one(seed, k))
+        .build(&keys)
+        .unwrap();
+    let generated = generate("set", |builder| {
+        let k = builder.create_u32_seq("Keys".into(), output.reorder(&keys).copied()).unwrap();
+        output.create_set("SET".into(), k, builder).unwrap();
+    });
+    assert!(generated.code.contains("const SET: precomputed_map::MediumSet<"), "{}", generated.code);
+    assert!(generated.code.contains("precomputed_map::MediumSet::new(42)"), "{}", generated.code);
+}
+
+#[test]
+fn test_dyn_map() {
+    use phf::{ HashOne, U64Hasher };
+    use crate::dynamic::{ DynMap, DynMediumMap, DynTinyMap };
+
+    type Hash = U64Hasher<DefaultHasher>;
+
+    for n in [0, 5, 10, 3000] {
+        let keys = (0..n).map(|i| format!("key{}", i)).collect::<Vec<_>>();
+        let values = (0..n).map(|i| format!("value{}", i)).collect::<Vec<_>>();
+        let mut builder = MapBuilder::new();
+        builder
+            .set_seed(42)
+            .set_hash(&|seed, k: &String| Hash::hash_one(seed, k.as_bytes()));
+        if n == 5 {
+            builder.set_ord(&|x: &String, y: &String| x.cmp(y));
+        }
+        let output = builder.build(&keys).unwrap();
+
+        let mut buf = Vec::new();
+        output.write_dyn_map(7, &keys, &values, &mut buf).unwrap();
+
+        let map = DynMap::<Hash>::load(&buf).unwrap();
+        assert_eq!(map.len(), n);
+        assert_eq!(map.hash_id(), 7);
+        for (k, v) in keys.iter().zip(values.iter()) {
+            assert_eq!(map.get(k.as_bytes()), Some(v.as_bytes()));
+        }
+        assert_eq!(map.get(b"key"), None);
+        assert_eq!(map.get(b"value0"), None);
+        assert_eq!(map.iter().len(), n);
+
+        match n {
+            5 => assert!(DynTinyMap::load(&buf).is_ok()),
+            3000 => assert!(DynMediumMap::<Hash>::load(&buf).is_ok()),
+            _ => ()
+        }
+
+        assert!(DynMap::<Hash>::load(&buf[..buf.len() / 2]).is_err());
+    }
+
+    // u16 pilots
+    let keys = (0..3000).map(|i| format!("key{}", i)).collect::<Vec<_>>();
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&|seed, k: &String| Hash::hash_one(seed, k.as_bytes()))
+        .set_pilot_width(Some(super::PilotWidth::U16))
+        .build(&keys)
+        .unwrap();
+    assert!(matches!(output.kind, MapKind::Medium { pilots: Pilots::U16(_), .. }));
+
+    let mut buf = Vec::new();
+    output.write_dyn_map(7, &keys, &keys, &mut buf).unwrap();
+    let map = DynMediumMap::<Hash>::load(&buf).unwrap();
+    for k in keys.iter() {
+        assert_eq!(map.get(k.as_bytes()), Some(k.as_bytes()));
+    }
+    assert_eq!(map.get(b"key"), None);
+
+    // the unsupported kinds are named
+    let mut builder = MapBuilder::new();
+    builder.set_seed(42).set_hash(&|seed, k: &String| Hash::hash_one(seed, k.as_bytes()));
+    let output = build::build_large(&builder, &keys, 1024).unwrap();
+    let err = output.write_dyn_map(7, &keys, &keys, &mut Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "large map is unsupported by dyn map");
+}
+
+#[test]
+fn test_container() {
+    use phf::HashOne;
+    use crate::container::{ self, Container, ContainerError };
+
+    assert_eq!(container::crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(container::crc32(b""), 0);
+
+    let mut buf = Vec::new();
+    ContainerWriter::new(7, 42)
+        .push_section(1, &b"hello"[..])
+        .push_section(2, Vec::new())
+        .push_section(3, &b"world"[..])
+        .write(&mut buf)
+        .unwrap();
+
+    let c = Container::parse(&buf).unwrap();
+    assert_eq!(c.hash_id(), 7);
+    assert_eq!(c.seed(), 42);
+    assert_eq!(c.sections().len(), 3);
+    assert_eq!(c.section(1), Some(&b"hello"[..]));
+    assert_eq!(c.section(2), Some(&b""[..]));
+    assert_eq!(c.section(3), Some(&b"world"[..]));
+    assert_eq!(c.section(4), None);
+
+    let mut bad = buf.clone();
+    *bad.last_mut().unwrap() ^= 1;
+    assert_eq!(Container::parse(&bad).err(), Some(ContainerError::ChecksumMismatch { tag: 3 }));
+    assert_eq!(Container::parse(&buf[..buf.len() - 1]).err(), Some(ContainerError::SectionOutOfBounds { tag: 3 }));
+    assert_eq!(Container::parse(&buf[..20]).err(), Some(ContainerError::Truncated));
+
+    let mut bad = buf.clone();
+    bad[0] = b'X';
+    assert_eq!(Container::parse(&bad).err(), Some(ContainerError::BadMagic));
+
+    let mut bad = buf.clone();
+    bad[8] = 2;
+    assert_eq!(Container::parse(&bad).err(), Some(ContainerError::UnsupportedVersion(2)));
+
+    // `CodeBuilder::write_container` writes the seed of each map in manifest,
+    // and the seed of the first one in header
+    let hash = |seed, k: &u32| phf::U64Hasher::<DefaultHasher>::hash_one(seed, k);
+    let tiny_keys = [5u32, 1, 3];
+    let small_keys = [3u32, 14, 15, 92];
+    let medium_keys = (0..3000).map(|k| k * 7).collect::<Vec<u32>>();
+
+    let tiny = MapBuilder::new().set_ord(&|x: &u32, y| x.cmp(y)).build(&tiny_keys).unwrap();
+    let small = MapBuilder::new().set_seed(42).set_hash(&hash).build(&small_keys).unwrap();
+    let medium = MapBuilder::new().set_seed(43).set_hash(&hash).build(&medium_keys).unwrap();
+    assert!(matches!(small.kind, MapKind::Small(_)));
+    assert!(matches!(medium.kind, MapKind::Medium { .. }));
+
+    let generated = generate("container", |builder| {
+        let k = builder.create_u32_seq("TinyKeys".into(), tiny.reorder(&tiny_keys).copied()).unwrap();
+        tiny.create_map("TINY".into(), k, builder).unwrap();
+        let k = builder.create_u32_seq("SmallKeys".into(), small.reorder(&small_keys).copied()).unwrap();
+        small.create_map("SMALL".into(), k, builder).unwrap();
+        let k = builder.create_u32_seq("MediumKeys".into(), medium.reorder(&medium_keys).copied()).unwrap();
+        medium.create_map("MEDIUM".into(), k, builder).unwrap();
+        let k = builder.create_keyless(&medium).unwrap();
+        medium.create_set("KEYLESS".into(), k, builder).unwrap();
+    });
+    assert_eq!(generated.hash_id, 7);
+    assert_eq!(Some(generated.seed), small.seed());
+    let MapKind::Medium { pilots, remap, .. } = &medium.kind else {
+        panic!("{:?}", medium.kind)
+    };
+    let (pilots_len, remap_len) = (pilot_list(pilots).len(), remap.len());
+
+    // every entry has a line, unnamed entries are referenced by index
+    let expected = [
+        "list TinyKeys 3".into(),
+        "map TINY tiny TinyKeys".into(),
+        "list SmallKeys 4".into(),
+        format!("map SMALL small {} SmallKeys", small.seed().unwrap()),
+        format!("u32seq MediumKeys 0 {}", medium_keys.len() * 4),
+        format!("list #5 {}", pilots_len),
+        format!("list #6 {}", remap_len),
+        format!("map MEDIUM medium {} #5 #6 MediumKeys", medium.seed().unwrap()),
+        format!("keyless #8 {}", medium_keys.len()),
+        format!("list #9 {}", pilots_len),
+        format!("list #10 {}", remap_len),
+        format!("set KEYLESS medium {} #9 #10 #8", medium.seed().unwrap()),
+    ];
+    assert_eq!(generated.manifest.lines().collect::<Vec<_>>(), expected);
+    // the offset and len are in bytes
+    let blob = medium.reorder(&medium_keys).flat_map(|k| k.to_le_bytes()).collect::<Vec<_>>();
+    assert_eq!(generated.u32seq[..blob.len()], blob);
+}
+
+#[test]
+fn test_dyn_map_load_invalid() {
+    use phf::{ HashOne, U64Hasher };
+    use crate::container::{ self, Container };
+    use crate::dynamic::{ DynMap, DynSmallMap, LoadError };
+
+    type Hash = U64Hasher<DefaultHasher>;
+
+    fn rewrite(buf: &[u8], f: impl Fn(u32, &[u8]) -> Option<Vec<u8>>) -> Vec<u8> {
+        let c = Container::parse(buf).unwrap();
+        let mut writer = ContainerWriter::new(c.hash_id(), c.seed());
+        for section in c.sections() {
+            if let Some(data) = f(section.tag, section.data) {
+                writer.push_section(section.tag, data);
+            }
+        }
+        let mut out = Vec::new();
+        writer.write(&mut out).unwrap();
+        out
+    }
+
+    fn u32s(list: &[u32]) -> Vec<u8> {
+        list.iter().flat_map(|n| n.to_le_bytes()).collect()
+    }
+
+    let keys = (0..3000).map(|i| format!("key{}", i)).collect::<Vec<_>>();
+    let output = MapBuilder::new()
+        .set_seed(42)
+        .set_hash(&|seed, k: &String| Hash::hash_one(seed, k.as_bytes()))
+        .build(&keys)
+        .unwrap();
+    let mut buf = Vec::new();
+    output.write_dyn_map(0, &keys, &keys, &mut buf).unwrap();
+    assert!(DynMap::<Hash>::load(&buf).is_ok());
+
+    let bad = rewrite(&buf, |tag, data| (tag != container::SECTION_DYN_KEYS).then(|| data.to_vec()));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::MissingSection { tag: container::SECTION_DYN_KEYS }));
+
+    let bad = rewrite(&buf, |tag, data| Some(match tag {
+        container::SECTION_DYN_KEY_ENDS => {
+            let mut data = data.to_vec();
+            data[..8].copy_from_slice(&u32s(&[5, 4]));
+            data
+        },
+        _ => data.to_vec()
+    }));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::BadEnds { tag: container::SECTION_DYN_KEY_ENDS }));
+
+    let bad = rewrite(&buf, |tag, data| Some(match tag {
+        container::SECTION_DYN_REMAP => u32s(&[3000]),
+        _ => data.to_vec()
+    }));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::BadIndex));
+
+    let bad = rewrite(&buf, |tag, data| Some(match tag {
+        container::SECTION_DYN_VALUE_ENDS => data[4..].to_vec(),
+        _ => data.to_vec()
+    }));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::BadSectionLength { tag: container::SECTION_DYN_VALUE_ENDS }));
+
+    let bad = rewrite(&buf, |tag, data| Some(match tag {
+        container::SECTION_DYN_META => u32s(&[9, 3000, 1]),
+        _ => data.to_vec()
+    }));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::UnknownKind(9)));
+
+    let bad = rewrite(&buf, |tag, data| Some(match tag {
+        container::SECTION_DYN_META => u32s(&[2, 3000, 4]),
+        _ => data.to_vec()
+    }));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::UnknownPilotWidth(4)));
+
+    let bad = rewrite(&buf, |tag, data| Some(match tag {
+        container::SECTION_DYN_META => u32s(&[0, 3000, 1]),
+        _ => data.to_vec()
+    }));
+    assert_eq!(DynMap::<Hash>::load(&bad).err(), Some(LoadError::Unsorted));
+
+    assert!(matches!(DynSmallMap::<Hash>::load(&buf), Err(LoadError::KindMismatch { .. })));
+    assert!(matches!(DynMap::<Hash>::load(&buf[1..]), Err(LoadError::Container(_))));
+
+    // Random sections with valid checksum never make lookup panic.
+    let mut next = xorshift(0x1234_5678);
+    for _ in 0..200 {
+        let target = [
+            container::SECTION_DYN_PILOTS,
+            container::SECTION_DYN_REMAP,
+            container::SECTION_DYN_KEY_ENDS,
+            container::SECTION_DYN_VALUES,
+        ][next() as usize % 4];
+        let fill = next();
+        let bad = rewrite(&buf, |tag, data| Some(if tag == target {
+            let len = fill as usize % (data.len() + 16);
+            (0..len).map(|i| (fill >> (i % 8 * 8)) as u8 ^ i as u8).collect()
+        } else {
+            data.to_vec()
+        }));
+
+        if let Ok(map) = DynMap::<Hash>::load(&bad) {
+            for k in keys.iter().take(100) {
+                let _ = map.get(k.as_bytes());
+            }
+            let _ = map.iter().count();
+        }
+    }
+}
+
+#[test]
+fn test_elias_fano_seq() {
+    use crate::store::AccessSeq;
+    use crate::seq::EliasFanoSeq;
+    use super::encode::EliasFano;
+
+    test_seq!(SparseSamples: [u32]);
+    test_seq!(SparseExceptions: [u32]);
+    test_seq!(SparseLow: [u32]);
+    test_seq!(SparseHigh: [u32]);
+    test_seq!(DenseSamples: [u32]);
+    test_seq!(DenseExceptions: [u32]);
+    test_seq!(DenseLow: [u32]);
+    test_seq!(DenseHigh: [u32]);
+    test_seq!(GapSamples: [u32]);
+    test_seq!(GapExceptions: [u32]);
+    test_seq!(GapLow: [u32]);
+    test_seq!(GapHigh: [u32]);
+
+    let mut next = xorshift(0x9e37_79b9);
+
+    // sparse, 37 per item on average
+    let mut sparse = Vec::new();
+    let mut n = 0;
+    for _ in 0..1000 {
+        n += (next() % 74) as u32;
+        sparse.push(n);
+    }
+    // dense, with duplicates
+    let dense = (0..1000).map(|i| i / 3).collect::<Vec<u32>>();
+    // a large gap in the middle, so a block of 64 spans many zeros of high bits
+    let gap = (0..1000).map(|i| if i < 500 { i } else { (1 << 24) + i }).collect::<Vec<u32>>();
+
+    let ef = EliasFano::encode(&sparse).unwrap();
+    assert!(ef.size() < sparse.len() * 4);
+    assert_eq!(ef.low_bits, 5);
+    assert!(ef.exceptions.is_empty());
+    SparseSamples::set(ef.samples);
+    SparseExceptions::set(ef.exceptions);
+    SparseLow::set(ef.low);
+    SparseHigh::set(ef.high);
+
+    let ef = EliasFano::encode(&dense).unwrap();
+    assert!(ef.size() < dense.len() * 4);
+    assert_eq!(ef.low_bits, 0);
+    DenseSamples::set(ef.samples);
+    DenseExceptions::set(ef.exceptions);
+    DenseLow::set(ef.low);
+    DenseHigh::set(ef.high);
+
+    // only the block of the gap stores the positions
+    let ef = EliasFano::encode(&gap).unwrap();
+    assert_eq!(ef.low_bits, 14);
+    assert_eq!(ef.exceptions.len(), 64);
+    GapSamples::set(ef.samples);
+    GapExceptions::set(ef.exceptions);
+    GapLow::set(ef.low);
+    GapHigh::set(ef.high);
+
+    type Sparse = EliasFanoSeq<1000, 5, SparseSamples, SparseExceptions, SparseLow, SparseHigh>;
+    type Dense = EliasFanoSeq<1000, 0, DenseSamples, DenseExceptions, DenseLow, DenseHigh>;
+    type Gap = EliasFanoSeq<1000, 14, GapSamples, GapExceptions, GapLow, GapHigh>;
+
+    for i in 0..1000 {
+        assert_eq!(Sparse::index(i), Some(sparse[i]), "{}", i);
+        assert_eq!(Dense::index(i), Some(dense[i]), "{}", i);
+        assert_eq!(Gap::index(i), Some(gap[i]), "{}", i);
+    }
+    assert_eq!(Sparse::index(1000), None);
+
+    assert!(EliasFano::encode(&[3, 2]).is_none());
+    assert!(EliasFano::encode(&[]).is_some());
+}
+
+#[test]
+fn test_packed_seq() {
+    use crate::store::AccessSeq;
+    use crate::seq::PackedSeq;
+    use super::encode::Packed;
+
+    test_seq!(IdsWords: [u32]);
+    test_seq!(OffsetsWords: [u32]);
+    test_seq!(WideWords: [u32]);
+
+    let mut next = xorshift(0x9e37_79b9_7f4a_7c15);
+
+    // 11 bit ids
+    let ids = (0..1000).map(|_| next() % 2000).collect::<Vec<u64>>();
+    // narrow range with large base
+    let mut offsets = (0..1000).map(|_| 3_000_000_000 + next() % 1000).collect::<Vec<u64>>();
+    offsets[500] = 3_000_000_000;
+    // full width
+    let wide = (0..1000).map(|_| next()).collect::<Vec<u64>>();
+
+    let packed = Packed::encode(&ids);
+    assert_eq!((packed.bits, packed.base), (11, 0));
+    IdsWords::set(packed.words);
+
+    let packed = Packed::encode(&offsets);
+    assert_eq!((packed.bits, packed.base), (10, 3_000_000_000));
+    OffsetsWords::set(packed.words);
+
+    let packed = Packed::encode(&wide);
+    assert_eq!((packed.bits, packed.base), (64, 0));
+    WideWords::set(packed.words);
+
+    type Ids = PackedSeq<1000, 11, 0, u32, IdsWords>;
+    type Ids64 = PackedSeq<1000, 11, 0, u64, IdsWords>;
+    type Offsets = PackedSeq<1000, 10, 3_000_000_000, u32, OffsetsWords>;
+    type Wide = PackedSeq<1000, 64, 0, u64, WideWords>;
+
+    for i in 0..1000 {
+        assert_eq!(Ids::index(i), Some(ids[i] as u32), "{}", i);
+        assert_eq!(Ids64::index(i), Some(ids[i]), "{}", i);
+        assert_eq!(Wide::index(i), Some(wide[i]), "{}", i);
+        assert_eq!(Offsets::index(i), Some(offsets[i] as u32), "{}", i);
+    }
+    assert_eq!(Ids::index(1000), None);
+    assert_eq!(Wide::index(1000), None);
+}
+
+#[test]
+fn test_escaped_seq() {
+    use crate::store::AccessSeq;
+    use crate::seq::EscapedSeq;
+    use super::encode::Escaped;
+
+    test_seq!(Codes: [u32]);
+    test_seq!(Samples: [u32]);
+    test_seq!(Exceptions: [u8]);
+
+    let mut next = xorshift(0x9e37_79b9);
+
+    // skewed toward small values
+    let list = (0..10_000)
+        .map(|_| match next() % 16 {
+            0 => (next() % 256) as u8,
+            _ => (next() % 7) as u8
+        })
+        .collect::<Vec<u8>>();
+
+    let escaped = Escaped::encode(&list);
+    assert_eq!(escaped.bits, 3);
+    assert!(escaped.size() < list.len() * 2 / 3, "{}", escaped.size());
+
+    Codes::set(escaped.codes);
+    Samples::set(escaped.samples);
+    Exceptions::set(escaped.exceptions);
+
+    type Seq = EscapedSeq<10_000, 3, Codes, Samples, Exceptions>;
+
+    for (i, &n) in list.iter().enumerate() {
+        assert_eq!(Seq::index(i), Some(n), "{}", i);
+    }
+    assert_eq!(Seq::index(10_000), None);
+}
+
+#[test]
+fn test_portable_hash() {
+    use phf::{ HashOne, Fx, Mix, Acc };
+    use crate::stable::Stable;
+
+    fn vectors<H: HashOne>(seed: u64) -> [u64; 7] {
+        [
+            H::hash_one(seed, 1u64),
+            H::hash_one(seed, 0x0123456789abcdef_u64),
+            H::hash_one(seed, 0xdeadbeef_u32),
+            H::hash_one(seed, ""),
+            H::hash_one(seed, "hello"),
+            H::hash_one(seed, b"the quick brown fox jumps over".as_slice()),
+            H::hash_one(seed, (1u128 << 100) + 5),
+        ]
+    }
+
+    // same as `rustc_hash::FxHasher` 1.x on 64-bit little-endian targets
+    assert_eq!(vectors::<Fx>(0), [
+        0x517cc1b727220a95, 0x56cc4aad99c8321b, 0x67f3c0372953771b, 0x2b44f56ffae88a6b,
+        0xd940de97f5011cc0, 0xd84e6c2a91cddf51, 0xa0a2317282e1721a,
+    ]);
+    assert_eq!(Fx::hash_one(0, 5usize), 0x976fc893c3aa34e9);
+    assert_eq!(Stable::<Fx>::hash_one(0, &[1u32, 2, 3][..]), 0x63cd0158ba8bc12d);
+    // integer slices are native bytes by `Hash`
+    #[cfg(target_endian = "little")]
+    assert_eq!(Fx::hash_one(0, &[1u32, 2, 3][..]), 0x17a075086413e85e);
+
+    // `Mix` and `Acc` are not published algorithms,
+    // these pin the output so that it does not change between versions.
+    assert_eq!(vectors::<Mix>(42), [
+        0x586d7d2c99217ef8, 0x41241b782329e621, 0xe8c65ed56245cf9c, 0xc781a6116418d08f,
+        0x1e0c66bee9378a47, 0x9e61f2d82c3987a2, 0x7c847e4f2d1b854d,
+    ]);
+    assert_eq!(vectors::<Acc>(42), [
+        0x469049402ccc76a2, 0xc5277e096582bb88, 0xbec70d07b6b5a773, 0x040db447341258cb,
+        0x7fe61d4bd3f25314, 0xcc5ea0c22d4979ab, 0x30763466bb91d6b9,
+    ]);
+    assert_eq!(Mix::hash_one(0, 5usize), 0x9a29c828062a3d5f);
+    assert_eq!(Stable::<Mix>::hash_one(0, &[1u32, 2, 3][..]), 0xdb61318d01358098);
+    assert_eq!(Acc::hash_one(0, 5usize), 0x3228b14613f30f4e);
+    assert_eq!(Stable::<Acc>::hash_one(0, &[1u32, 2, 3][..]), 0x8b3ba857b33ed711);
+
+    // usize and isize are hashed as u64
+    assert_eq!(Mix::hash_one(3, 5usize), Mix::hash_one(3, 5u64));
+    assert_eq!(Mix::hash_one(3, -5isize), Mix::hash_one(3, -5i64));
+
+    let keys = (0..10 * 1024).map(|n| format!("key{}", n)).collect::<Vec<_>>();
+    let hashes: [HashFunc<'_, String>; 3] = [
+        &|seed, k| Fx::hash_one(seed, k),
+        &|seed, k| Mix::hash_one(seed, k),
+        &|seed, k| Acc::hash_one(seed, k),
+    ];
+
+    for hash in hashes {
+        let output = MapBuilder::<String>::deterministic()
+            .set_hash(hash)
+            .set_limit(Some(16))
+            .build(&keys)
+            .unwrap();
+        assert!(matches!(output.kind, MapKind::Medium { .. }));
+    }
+}
+
+#[test]
+fn test_stable_hash() {
+    use phf::Mix;
+    use crate::store::AccessSeq;
+    use crate::stable::{ Stable, StableHash };
+
+    #[derive(Default)]
+    struct Record(Vec<Vec<u8>>);
+
+    impl Hasher for Record {
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.push(bytes.to_vec());
+        }
+
+        fn finish(&self) -> u64 {
+            0
+        }
+    }
+
+    let mut record = Record::default();
+    let key = ((1u16, "ab", [3u8, 4], -1i8), ('x', true, 5usize, [1u32, 2].as_slice()));
+    key.stable_hash(&mut record);
+    assert_eq!(record.0, [
+        vec![1, 0],
+        vec![2, 0, 0, 0, 0, 0, 0, 0], b"ab".to_vec(),
+        vec![3, 4],
+        vec![0xff],
+        vec![b'x', 0, 0, 0],
+        vec![1],
+        vec![5, 0, 0, 0, 0, 0, 0, 0],
+        vec![2, 0, 0, 0, 0, 0, 0, 0], vec![1, 0, 0, 0], vec![2, 0, 0, 0],
+    ]);
+
+    // str is same as bytes
+    assert_eq!(Stable::<Mix>::hash_one(42, "hello"), Stable::<Mix>::hash_one(42, b"hello".as_slice()));
+    assert_eq!(Stable::<Mix>::hash_one(42, "hello"), Stable::<Mix>::hash_one(42, &String::from("hello")));
+
+    test_seq!(Keys: [&'static str; 10]);
+
+    let keys = (0..10).map(|n| format!("key{}", n)).collect::<Vec<_>>();
+    let output = MapBuilder::<String>::new()
+        .set_seed(42)
+        .set_stable_hash::<Mix>()
+        .build(&keys)
+        .unwrap();
+    Keys::set(output.reorder(&keys).map(|k| &*k.clone().leak()));
+
+    let map = crate::SmallMap::<Keys, Stable<Mix>>::new(output.seed().unwrap());
+    for key in keys.iter() {
+        let idx = map.get(key.as_str()).unwrap();
+        assert_eq!(Keys::index(idx), Some(key.as_str()));
+    }
+    assert_eq!(map.get("key10"), None);
+}
+
+#[test]
+fn test_str_keys() {
+    use phf::{ HashOne, Mix };
+    use crate::MediumMap;
+    use crate::store::{ AccessSeq, AsData };
+    use crate::seq::{ PositionSeq, StrPositionSeq };
+    use crate::equivalent::{ AsBytes, AsStr, Hashable };
+    use crate::stable::Stable;
+
+    // more than 16 keys, so they are written as position seq
+    const KEYS_LEN: usize = 20;
+    const BYTES_LEN: usize = 58;
+    const PILOTS_LEN: usize = 10;
+    const REMAP_LEN: usize = 1;
+
+    test_seq!(StrEnds: [u32; KEYS_LEN]);
+    test_seq!(StrBuf: bytes [BYTES_LEN]);
+    test_seq!(StrPilots: [u16; PILOTS_LEN]);
+    test_seq!(StrRemap: [u32; REMAP_LEN]);
+    test_seq!(BytesEnds: [u32; KEYS_LEN]);
+    test_seq!(BytesBuf: bytes [BYTES_LEN]);
+    test_seq!(BytesPilots: [u16; PILOTS_LEN]);
+    test_seq!(BytesRemap: [u32; REMAP_LEN]);
+    test_seq!(StableEnds: [u32; KEYS_LEN]);
+    test_seq!(StableBuf: bytes [BYTES_LEN]);
+    test_seq!(StablePilots: [u16; PILOTS_LEN]);
+    test_seq!(StableRemap: [u32; REMAP_LEN]);
+
+    fn check_str<P, R, E, B, H>(seed: u64, keys: &[String])
+    where
+        P: AccessSeq<Item = u16>,
+        R: AccessSeq<Item = u32>,
+        E: AccessSeq<Item = u32>,
+        B: AsData<Data = [u8; BYTES_LEN]>,
+        str: Hashable<H>,
+        for<'a> AsStr<'a>: Hashable<H>,
+    {
+        let map = MediumMap::<P, R, StrPositionSeq<E, B>, H>::new(seed);
+
+        for key in keys {
+            let idx = map.get(key.as_str()).unwrap();
+            assert_eq!(StrPositionSeq::<E, B>::index(idx), Some(key.as_str()));
+            assert_eq!(map.get(&AsStr(key.as_bytes())), Some(idx));
+        }
+        assert_eq!(map.get("foo2"), None);
+        assert_eq!(map.get(&AsStr(b"ba\xffr")), None);
+    }
+
+    fn check_bytes<P, R, E, B, H>(seed: u64, keys: &[String])
+    where
+        P: AccessSeq<Item = u16>,
+        R: AccessSeq<Item = u32>,
+        E: AccessSeq<Item = u32>,
+        B: AsData<Data = [u8; BYTES_LEN]>,
+        [u8]: Hashable<H>,
+        for<'a> AsBytes<'a>: Hashable<H>,
+    {
+        let map = MediumMap::<P, R, PositionSeq<E, B>, H>::new(seed);
+
+        for key in keys {
+            let idx = map.get(key.as_bytes()).unwrap();
+            assert_eq!(PositionSeq::<E, B>::index(idx), Some(key.as_bytes()));
+            assert_eq!(map.get(&AsBytes(key)), Some(idx));
+        }
+        assert_eq!(map.get(&AsBytes("fo")), None);
+    }
+
+    struct Stores {
+        seed: u64,
+        pilots: Vec<u16>,
+        remap: Vec<u32>,
+        ends: Vec<u32>,
+        buf: Vec<u8>,
+    }
+
+    /// Generates the keys and map, the key buffer is read back from container.
+    fn generate_keys(name: &str, output: &super::MapOutput, keys: &[String], str: bool) -> Stores {
+        let generated = generate(name, |builder| {
+            let k = match str {
+                true => builder.create_str_keys("Keys".into(), output, output.reorder(keys)),
+                false => builder.create_bytes_keys("Keys".into(), output, output.reorder(keys).map(String::as_bytes))
+            };
+            output.create_map("MAP".into(), k.unwrap(), builder).unwrap();
+        });
+
+        let (seq, kind) = match str {
+            true => ("seq::StrPositionSeq<", "str"),
+            false => ("seq::PositionSeq<", "bytes")
+        };
+        assert!(generated.code.contains(seq), "{}", generated.code);
+        // the positions are the unnamed entry #4, after the samples, exceptions, low and high of it
+        let line = format!("{} Keys 0 {} #4\n", kind, BYTES_LEN);
+        assert!(generated.manifest.contains(&line), "{}", generated.manifest);
+
+        let MapKind::Medium { seed, pilots, remap } = &output.kind else {
+            panic!("{:?}", output.kind)
+        };
+        let ends = output.reorder(keys)
+            .scan(0, |end, key| {
+                *end += key.len() as u32;
+                Some(*end)
+            })
+            .collect();
+
+        Stores {
+            seed: *seed,
+            pilots: pilot_list(pilots),
+            remap: remap.to_vec(),
+            ends,
+            buf: generated.u8seq,
+        }
+    }
+
+    let mut keys = ["foo", "bar", "", "\u{3b1}\u{3b2}", "bazqux\u{ff}"].map(String::from).to_vec();
+    keys.extend((5..KEYS_LEN).map(|n| format!("k{}", n)));
+    let bytes_keys = keys.iter().map(String::as_bytes).collect::<Vec<_>>();
+
+    // `Hash` writes str and bytes differently, so each needs its own map
+    let output = MapBuilder::<String>::new()
+        .set_seed(42)
+        .set_hash(&|seed, k| Mix::hash_one(seed, k.as_str()))
+        .build(&keys)
+        .unwrap();
+    let stores = generate_keys("str-keys", &output, &keys, true);
+    StrPilots::set(stores.pilots);
+    StrRemap::set(stores.remap);
+    StrEnds::set(stores.ends);
+    StrBuf::set(stores.buf);
+    check_str::<StrPilots, StrRemap, StrEnds, StrBuf, Mix>(stores.seed, &keys);
+
+    let output = MapBuilder::<&[u8]>::new()
+        .set_seed(42)
+        .set_hash(&|seed, k| Mix::hash_one(seed, k))
+        .build(&bytes_keys)
+        .unwrap();
+    let stores = generate_keys("bytes-keys", &output, &keys, false);
+    BytesPilots::set(stores.pilots);
+    BytesRemap::set(stores.remap);
+    BytesEnds::set(stores.ends);
+    BytesBuf::set(stores.buf);
+    check_bytes::<BytesPilots, BytesRemap, BytesEnds, BytesBuf, Mix>(stores.seed, &keys);
+
+    // `StableHash` writes str same as bytes, so one map works for both
+    let output = MapBuilder::<String>::new()
+        .set_seed(42)
+        .set_stable_hash::<Mix>()
+        .build(&keys)
+        .unwrap();
+    let stores = generate_keys("stable-keys", &output, &keys, true);
+    StablePilots::set(stores.pilots);
+    StableRemap::set(stores.remap);
+    StableEnds::set(stores.ends);
+    StableBuf::set(stores.buf);
+    check_str::<StablePilots, StableRemap, StableEnds, StableBuf, Stable<Mix>>(stores.seed, &keys);
+    check_bytes::<StablePilots, StableRemap, StableEnds, StableBuf, Stable<Mix>>(stores.seed, &keys);
+
+    // a few keys are written as str literals
+    let output = MapBuilder::<String>::new()
+        .set_ord(&|x, y| x.cmp(y))
+        .build(&keys[..5])
+        .unwrap();
+    let generated = generate("str-list", |builder| {
+        let k = builder.create_str_keys("Keys".into(), &output, output.reorder(&keys[..5])).unwrap();
+        output.create_map("MAP".into(), k, builder).unwrap();
+    });
+    assert!(generated.code.contains("&[&'static str; 5] = &[\"\",\"bar\",\"bazqux\u{ff}\",\"foo\",\"\u{3b1}\u{3b2}\",]"), "{}", generated.code);
+    assert!(!generated.manifest.contains("str Keys"), "{}", generated.manifest);
+}
+
+#[test]
+fn test_ascii_case_insensitive() {
+    use phf::{ HashOne, Mix };
+    use crate::store::AccessSeq;
+    use crate::equivalent::{ AsciiCaseInsensitive, Comparable };
+    use crate::stable::Stable;
+
+    test_seq!(Keys: [&'static str; 6]);
+
+    let keys = ["Content-Type", "content-length", "HOST", "Accept", "X-Request-Id-With-A-Long-Name-Over-32", "te"]
+        .map(String::from);
+    let output = MapBuilder::<String>::new()
+        .set_seed(42)
+        .set_normalize(&|k| k.to_ascii_lowercase())
+        .set_hash(&|seed, k| Mix::hash_one(seed, AsciiCaseInsensitive(k)))
+        .build(&keys)
+        .unwrap();
+    Keys::set(output.reorder(&keys).map(|k| &*k.to_ascii_lowercase().leak()));
+
+    let map = crate::SmallMap::<Keys, Mix>::new(output.seed().unwrap());
+    for key in keys.iter() {
+        for query in [key.clone(), key.to_ascii_lowercase(), key.to_ascii_uppercase()] {
+            let idx = map.get(&AsciiCaseInsensitive(query.as_str())).unwrap();
+            assert_eq!(Keys::index(idx), Some(key.to_ascii_lowercase().as_str()));
+        }
+    }
+    assert_eq!(map.get(&AsciiCaseInsensitive("content-typ")), None);
+    assert_eq!(map.get(&AsciiCaseInsensitive(b"HOSTS")), None);
+
+    // hash and order are the same as lowercase
+    let long = "X-Request-Id-With-A-Long-Name-Over-32";
+    assert_eq!(
+        Mix::hash_one(1, AsciiCaseInsensitive(long)),
+        Mix::hash_one(1, AsciiCaseInsensitive(long.to_ascii_lowercase()))
+    );
+    assert_eq!(
+        Stable::<Mix>::hash_one(1, &AsciiCaseInsensitive(long)),
+        Stable::<Mix>::hash_one(1, &AsciiCaseInsensitive(long.to_ascii_lowercase()))
+    );
+    assert_ne!(Mix::hash_one(1, AsciiCaseInsensitive("ab")), Mix::hash_one(1, AsciiCaseInsensitive("abc")));
+    assert_eq!(AsciiCaseInsensitive("ABC").compare(&"abd"), std::cmp::Ordering::Less);
+    assert_eq!(AsciiCaseInsensitive("B").compare(&"a"), std::cmp::Ordering::Greater);
+
+    // collide after normalization
+    let keys = ["Accept", "Host", "ACCEPT"].map(String::from);
+    let err = MapBuilder::<String>::new()
+        .set_normalize(&|k| k.to_ascii_lowercase())
+        .set_hash(&|seed, k| Mix::hash_one(seed, AsciiCaseInsensitive(k)))
+        .build(&keys)
+        .unwrap_err();
+    assert_eq!(err, BuildFailed::DuplicateKey { first: 0, second: 2 });
+}
+
+#[test]
+fn test_build_duplicate() {
+    use phf::{ HashOne, Mix };
+
+    let hash: HashFunc<u64> = &|seed, k| Mix::hash_one(seed, k);
+    let hash_wide = |k: &u64| u128::from(Mix::hash_one(0, k)) << 64 | u128::from(*k);
+    let ord = |x: &u64, y: &u64| x.cmp(y);
+
+    // tiny and ordered
+    let keys = [1u64, 5, 3, 5];
+    let err = MapBuilder::new().set_ord(&ord).build(&keys).unwrap_err();
+    assert_eq!(err, BuildFailed::DuplicateKey { first: 1, second: 3 });
+    let err = MapBuilder::new().set_ord(&ord).build_ordered(&keys).unwrap_err();
+    assert_eq!(err, BuildFailed::DuplicateKey { first: 1, second: 3 });
+    assert_eq!(MapBuilder::new().build_ordered(&keys).unwrap_err(), BuildFailed::MissingOrd);
+    assert_eq!(MapBuilder::new().build(&keys).unwrap_err(), BuildFailed::MissingHash);
+
+    // small, medium and large
+    for len in [40, 1000] {
+        let mut keys = (0..len).map(|n| n * 7).collect::<Vec<u64>>();
+        keys[len as usize / 2] = keys[3];
+
+        let err = MapBuilder::new().set_hash(&hash).build(&keys).unwrap_err();
+        assert_eq!(err, BuildFailed::HashCollision { first: 3, second: len as usize / 2 });
+        let err = MapBuilder::new().set_hash(&hash).set_ord(&ord).build(&keys).unwrap_err();
+        assert_eq!(err, BuildFailed::DuplicateKey { first: 3, second: len as usize / 2 });
+        let err = MapBuilder::new().set_hash_wide(&hash_wide).build(&keys).unwrap_err();
+        assert_eq!(err, BuildFailed::HashCollision { first: 3, second: len as usize / 2 });
+    }
+
+    let mut keys = (0..200 * 1024).collect::<Vec<u64>>();
+    keys[100] = 7;
+    let mut builder = MapBuilder::new();
+    builder.set_hash(&hash);
+    let err = build::build_large(&builder, &keys, 64 * 1024).unwrap_err();
+    assert_eq!(err, BuildFailed::HashCollision { first: 7, second: 100 });
+
+    // hash that ignores part of key
+    let keys = (0..1000).collect::<Vec<u64>>();
+    let err = MapBuilder::new()
+        .set_hash(&|seed, k| Mix::hash_one(seed, k / 2))
+        .build(&keys)
+        .unwrap_err();
+    assert_eq!(err, BuildFailed::HashCollision { first: 0, second: 1 });
+
+    // unique keys that no seed within limit works
+    let err = MapBuilder::new()
+        .set_hash(&hash)
+        .set_params(super::PhfParams { alpha: 1.0, lambda: 10.0 })
+        .set_pilot_width(Some(super::PilotWidth::U8))
+        .set_limit(Some(0))
+        .build(&keys)
+        .unwrap_err();
+    assert_eq!(err, BuildFailed::LimitExhausted { limit: 0 });
+    assert_eq!(err.to_string(), "no seed found within limit 0");
+}
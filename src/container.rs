@@ -0,0 +1,193 @@
+//! Container format of map data
+//!
+//! A self-describing file made of sections, all integers are little-endian:
+//!
+//! ```text
+//! magic: [u8; 8], version: u32, hash_id: u32, seed: u64, sections_len: u32, reserved: u32
+//! sections: [(tag: u32, offset: u32, len: u32, crc32: u32); sections_len]
+//! data of sections
+//! ```
+//!
+//! The offset of section is counted from the start of file.
+
+use core::fmt;
+
+pub const MAGIC: [u8; 8] = *b"PCMAPBIN";
+pub const VERSION: u32 = 1;
+
+/// The u8 seq blob of code builder
+pub const SECTION_U8SEQ: u32 = 0x01;
+/// The u32 seq blob of code builder
+pub const SECTION_U32SEQ: u32 = 0x02;
+/// Text manifest of code builder entries
+pub const SECTION_MANIFEST: u32 = 0x03;
+
+/// Kind and length of dyn map
+pub const SECTION_DYN_META: u32 = 0x10;
+pub const SECTION_DYN_PILOTS: u32 = 0x11;
+pub const SECTION_DYN_REMAP: u32 = 0x12;
+pub const SECTION_DYN_KEY_ENDS: u32 = 0x13;
+pub const SECTION_DYN_VALUE_ENDS: u32 = 0x14;
+pub const SECTION_DYN_KEYS: u32 = 0x15;
+pub const SECTION_DYN_VALUES: u32 = 0x16;
+
+pub(crate) const HEADER_LEN: usize = 8 + 4 + 4 + 8 + 4 + 4;
+pub(crate) const ENTRY_LEN: usize = 4 * 4;
+
+/// Validated container
+#[derive(Clone, Copy)]
+pub struct Container<'a> {
+    buf: &'a [u8],
+    hash_id: u32,
+    seed: u64,
+    sections_len: usize,
+}
+
+/// Section of container
+#[derive(Debug, Clone, Copy)]
+pub struct Section<'a> {
+    pub tag: u32,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u32),
+    SectionOutOfBounds { tag: u32 },
+    ChecksumMismatch { tag: u32 },
+}
+
+impl<'a> Container<'a> {
+    /// Parse container and verify the checksum of all sections.
+    pub fn parse(buf: &'a [u8]) -> Result<Self, ContainerError> {
+        let header = buf.get(..HEADER_LEN).ok_or(ContainerError::Truncated)?;
+
+        if header[..8] != MAGIC {
+            return Err(ContainerError::BadMagic);
+        }
+
+        let version = read_u32(header, 8);
+        if version != VERSION {
+            return Err(ContainerError::UnsupportedVersion(version));
+        }
+
+        let hash_id = read_u32(header, 12);
+        let seed = u64::from_le_bytes([
+            header[16], header[17], header[18], header[19],
+            header[20], header[21], header[22], header[23],
+        ]);
+        let sections_len: usize = read_u32(header, 24)
+            .try_into()
+            .map_err(|_| ContainerError::Truncated)?;
+
+        let table_len = sections_len.checked_mul(ENTRY_LEN).ok_or(ContainerError::Truncated)?;
+        if buf.len() - HEADER_LEN < table_len {
+            return Err(ContainerError::Truncated);
+        }
+
+        let container = Container { buf, hash_id, seed, sections_len };
+
+        for idx in 0..sections_len {
+            let entry = &buf[HEADER_LEN + idx * ENTRY_LEN..][..ENTRY_LEN];
+            let tag = read_u32(entry, 0);
+            let data = container.section_data(entry)
+                .ok_or(ContainerError::SectionOutOfBounds { tag })?;
+
+            if crc32(data) != read_u32(entry, 12) {
+                return Err(ContainerError::ChecksumMismatch { tag });
+            }
+        }
+
+        Ok(container)
+    }
+
+    /// The id of hash algorithm, defined by user.
+    pub fn hash_id(&self) -> u32 {
+        self.hash_id
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn sections(&self) -> impl ExactSizeIterator<Item = Section<'a>> + '_ {
+        (0..self.sections_len).map(|idx| {
+            let entry = &self.buf[HEADER_LEN + idx * ENTRY_LEN..][..ENTRY_LEN];
+            Section {
+                tag: read_u32(entry, 0),
+                data: self.section_data(entry).unwrap_or_default()
+            }
+        })
+    }
+
+    /// Returns the data of first section with tag.
+    pub fn section(&self, tag: u32) -> Option<&'a [u8]> {
+        self.sections()
+            .find(|section| section.tag == tag)
+            .map(|section| section.data)
+    }
+
+    fn section_data(&self, entry: &[u8]) -> Option<&'a [u8]> {
+        let offset: usize = read_u32(entry, 4).try_into().ok()?;
+        let len: usize = read_u32(entry, 8).try_into().ok()?;
+        self.buf.get(offset..)?.get(..len)
+    }
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::Truncated => f.write_str("container truncated"),
+            ContainerError::BadMagic => f.write_str("bad container magic"),
+            ContainerError::UnsupportedVersion(version) =>
+                write!(f, "unsupported container version: {}", version),
+            ContainerError::SectionOutOfBounds { tag } =>
+                write!(f, "section {:#x} out of bounds", tag),
+            ContainerError::ChecksumMismatch { tag } =>
+                write!(f, "section {:#x} checksum mismatch", tag),
+        }
+    }
+}
+
+impl core::error::Error for ContainerError {}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+/// CRC-32 (IEEE)
+pub fn crc32(buf: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &b in buf {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
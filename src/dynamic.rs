@@ -0,0 +1,474 @@
+//! Runtime loaded maps
+//!
+//! Unlike the type-level maps, these borrow the map data from a byte buffer at runtime,
+//! so the data can be updated without recompiling.
+//! Both keys and values are bytes.
+//!
+//! The data file is a [`container`](crate::container) written by `MapOutput::write_dyn_map`,
+//! the seed and hash id are stored in container header, and the map is stored in sections:
+//!
+//! ```text
+//! SECTION_DYN_META: kind: u32, len: u32, pilot_width: u32
+//! SECTION_DYN_PILOTS: [u8; pilots_len] or [u16; pilots_len], by pilot_width in bytes
+//! SECTION_DYN_REMAP: [u32; remap_len]
+//! SECTION_DYN_KEY_ENDS, SECTION_DYN_VALUE_ENDS: [u32; len]
+//! SECTION_DYN_KEYS, SECTION_DYN_VALUES: [u8]
+//! ```
+//!
+//! The data is fully validated when loading, so it is safe to load untrusted data,
+//! lookups never panic.
+
+use core::fmt;
+use core::marker::PhantomData;
+use crate::phf::{ self, HashOne };
+use crate::container::{ self, Container, ContainerError };
+use crate::{ fast_reduct32, low, high };
+
+pub(crate) const KIND_TINY: u32 = 0;
+pub(crate) const KIND_SMALL: u32 = 1;
+pub(crate) const KIND_MEDIUM: u32 = 2;
+
+/// Error of loading map data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    Container(ContainerError),
+    MissingSection { tag: u32 },
+    /// The section length does not match the map
+    BadSectionLength { tag: u32 },
+    /// The ends of bytes are decreasing or out of bounds
+    BadEnds { tag: u32 },
+    UnknownKind(u32),
+    /// The pilot width is neither 1 nor 2 bytes
+    UnknownPilotWidth(u32),
+    KindMismatch { expected: u32, found: u32 },
+    /// The remap points outside the keys, or the map has no pilots
+    BadIndex,
+    /// The keys of tiny map are not sorted
+    Unsorted,
+}
+
+/// Runtime loaded map of any kind
+pub enum DynMap<'a, H> {
+    Tiny(DynTinyMap<'a>),
+    Small(DynSmallMap<'a, H>),
+    Medium(DynMediumMap<'a, H>),
+}
+
+/// Runtime loaded tiny map
+///
+/// Keys are sorted in bytes order.
+pub struct DynTinyMap<'a> {
+    hash_id: u32,
+    data: DynData<'a>
+}
+
+/// Runtime loaded small map
+pub struct DynSmallMap<'a, H> {
+    hash_id: u32,
+    seed: u64,
+    data: DynData<'a>,
+    _phantom: PhantomData<H>
+}
+
+/// Runtime loaded medium map
+pub struct DynMediumMap<'a, H> {
+    hash_id: u32,
+    seed: u64,
+    pilots: &'a [u8],
+    pilot_width: usize,
+    remap: &'a [u8],
+    data: DynData<'a>,
+    _phantom: PhantomData<H>
+}
+
+struct Header<'a> {
+    kind: u32,
+    hash_id: u32,
+    seed: u64,
+    pilots: &'a [u8],
+    pilot_width: usize,
+    remap: &'a [u8],
+    data: DynData<'a>,
+}
+
+struct DynData<'a> {
+    keys: BytesSeq<'a>,
+    values: BytesSeq<'a>,
+}
+
+struct BytesSeq<'a> {
+    ends: &'a [u8],
+    buf: &'a [u8],
+}
+
+impl<'a, H: HashOne> DynMap<'a, H> {
+    pub fn load(buf: &'a [u8]) -> Result<Self, LoadError> {
+        let header = Header::load(buf)?;
+
+        match header.kind {
+            KIND_TINY => Ok(DynMap::Tiny(DynTinyMap::from_header(header))),
+            KIND_SMALL => Ok(DynMap::Small(DynSmallMap::from_header(header))),
+            KIND_MEDIUM => Ok(DynMap::Medium(DynMediumMap::from_header(header))),
+            kind => Err(LoadError::UnknownKind(kind))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            DynMap::Tiny(map) => map.len(),
+            DynMap::Small(map) => map.len(),
+            DynMap::Medium(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `hash_id` passed to `write_dyn_map`, to check that `H` is the hash of data
+    pub fn hash_id(&self) -> u32 {
+        match self {
+            DynMap::Tiny(map) => map.hash_id(),
+            DynMap::Small(map) => map.hash_id(),
+            DynMap::Medium(map) => map.hash_id(),
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        match self {
+            DynMap::Tiny(map) => map.get(key),
+            DynMap::Small(map) => map.get(key),
+            DynMap::Medium(map) => map.get(key),
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&'a [u8], &'a [u8])> + '_ {
+        let data = match self {
+            DynMap::Tiny(map) => &map.data,
+            DynMap::Small(map) => &map.data,
+            DynMap::Medium(map) => &map.data,
+        };
+        data.iter()
+    }
+}
+
+impl<'a> DynTinyMap<'a> {
+    pub fn load(buf: &'a [u8]) -> Result<Self, LoadError> {
+        let header = Header::load(buf)?.expect_kind(KIND_TINY)?;
+        Ok(DynTinyMap::from_header(header))
+    }
+
+    fn from_header(header: Header<'a>) -> Self {
+        DynTinyMap {
+            hash_id: header.hash_id,
+            data: header.data
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// See [`DynMap::hash_id`]
+    pub fn hash_id(&self) -> u32 {
+        self.hash_id
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        let idx = crate::store::partition_point(self.len(), |idx| self.data.keys.get(idx)
+            .is_some_and(|k| k < key)
+        );
+
+        if self.data.keys.get(idx)? == key {
+            self.data.values.get(idx)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&'a [u8], &'a [u8])> + '_ {
+        self.data.iter()
+    }
+}
+
+impl<'a, H: HashOne> DynSmallMap<'a, H> {
+    pub fn load(buf: &'a [u8]) -> Result<Self, LoadError> {
+        let header = Header::load(buf)?.expect_kind(KIND_SMALL)?;
+        Ok(DynSmallMap::from_header(header))
+    }
+
+    fn from_header(header: Header<'a>) -> Self {
+        DynSmallMap {
+            hash_id: header.hash_id,
+            seed: header.seed,
+            data: header.data,
+            _phantom: PhantomData
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// See [`DynMap::hash_id`]
+    pub fn hash_id(&self) -> u32 {
+        self.hash_id
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        let size: u32 = self.len().try_into().ok()?;
+
+        if size == 0 {
+            return None;
+        }
+
+        let hash = H::hash_one(self.seed, key);
+        let index: usize = fast_reduct32(high(hash) ^ low(hash), size).try_into().ok()?;
+        self.data.get(index, key)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&'a [u8], &'a [u8])> + '_ {
+        self.data.iter()
+    }
+}
+
+impl<'a, H: HashOne> DynMediumMap<'a, H> {
+    pub fn load(buf: &'a [u8]) -> Result<Self, LoadError> {
+        let header = Header::load(buf)?.expect_kind(KIND_MEDIUM)?;
+        Ok(DynMediumMap::from_header(header))
+    }
+
+    fn from_header(header: Header<'a>) -> Self {
+        DynMediumMap {
+            hash_id: header.hash_id,
+            seed: header.seed,
+            pilots: header.pilots,
+            pilot_width: header.pilot_width,
+            remap: header.remap,
+            data: header.data,
+            _phantom: PhantomData
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// See [`DynMap::hash_id`]
+    pub fn hash_id(&self) -> u32 {
+        self.hash_id
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let pilots_len: u32 = (self.pilots.len() / self.pilot_width).try_into().ok()?;
+        let slots_len: u32 = (self.len() + self.remap.len() / 4).try_into().ok()?;
+
+        let hash = H::hash_one(self.seed, key);
+        let bucket: usize = fast_reduct32(low(hash), pilots_len).try_into().ok()?;
+        let pilot = match self.pilot_width {
+            1 => u16::from(*self.pilots.get(bucket)?),
+            _ => {
+                let buf = self.pilots.get(bucket.checked_mul(2)?..)?.get(..2)?;
+                u16::from_le_bytes([buf[0], buf[1]])
+            }
+        };
+        let pilot_hash = phf::hash_pilot(self.seed, pilot);
+
+        let index: usize = fast_reduct32(
+            high(hash) ^ high(pilot_hash) ^ low(pilot_hash),
+            slots_len
+        ).try_into().ok()?;
+
+        let index = match index.checked_sub(self.len()) {
+            None => index,
+            Some(remap) => read_u32(self.remap, remap)?.try_into().ok()?
+        };
+
+        self.data.get(index, key)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&'a [u8], &'a [u8])> + '_ {
+        self.data.iter()
+    }
+}
+
+impl<'a> Header<'a> {
+    fn load(buf: &'a [u8]) -> Result<Self, LoadError> {
+        let container = Container::parse(buf)?;
+        let section = |tag| container.section(tag).ok_or(LoadError::MissingSection { tag });
+
+        let meta = section(container::SECTION_DYN_META)?;
+        let (12, Some(kind), Some(len), Some(pilot_width)) =
+            (meta.len(), read_u32(meta, 0), read_u32(meta, 1), read_u32(meta, 2))
+        else {
+            return Err(LoadError::BadSectionLength { tag: container::SECTION_DYN_META });
+        };
+
+        if !matches!(kind, KIND_TINY | KIND_SMALL | KIND_MEDIUM) {
+            return Err(LoadError::UnknownKind(kind));
+        }
+
+        if !matches!(pilot_width, 1 | 2) {
+            return Err(LoadError::UnknownPilotWidth(pilot_width));
+        }
+
+        let pilots = container.section(container::SECTION_DYN_PILOTS).unwrap_or_default();
+        let remap = container.section(container::SECTION_DYN_REMAP).unwrap_or_default();
+        let keys = BytesSeq::load(
+            len,
+            section(container::SECTION_DYN_KEY_ENDS)?,
+            container::SECTION_DYN_KEY_ENDS,
+            section(container::SECTION_DYN_KEYS)?
+        )?;
+        let values = BytesSeq::load(
+            len,
+            section(container::SECTION_DYN_VALUE_ENDS)?,
+            container::SECTION_DYN_VALUE_ENDS,
+            section(container::SECTION_DYN_VALUES)?
+        )?;
+
+        if !pilots.len().is_multiple_of(pilot_width as usize) {
+            return Err(LoadError::BadSectionLength { tag: container::SECTION_DYN_PILOTS });
+        }
+
+        if !remap.len().is_multiple_of(4)
+            || u32::try_from(remap.len() / 4).ok()
+                .and_then(|n| n.checked_add(len))
+                .is_none()
+        {
+            return Err(LoadError::BadSectionLength { tag: container::SECTION_DYN_REMAP });
+        }
+
+        if kind == KIND_MEDIUM && len != 0 && pilots.is_empty() {
+            return Err(LoadError::BadIndex);
+        }
+
+        if (0..remap.len() / 4).any(|idx| read_u32(remap, idx).is_none_or(|n| n >= len)) {
+            return Err(LoadError::BadIndex);
+        }
+
+        if kind == KIND_TINY && !(1..keys.len()).all(|idx| keys.get(idx - 1) < keys.get(idx)) {
+            return Err(LoadError::Unsorted);
+        }
+
+        Ok(Header {
+            kind,
+            hash_id: container.hash_id(),
+            seed: container.seed(),
+            pilots,
+            pilot_width: pilot_width as usize,
+            remap,
+            data: DynData { keys, values }
+        })
+    }
+
+    fn expect_kind(self, expected: u32) -> Result<Self, LoadError> {
+        if self.kind == expected {
+            Ok(self)
+        } else {
+            Err(LoadError::KindMismatch { expected, found: self.kind })
+        }
+    }
+}
+
+impl<'a> DynData<'a> {
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    fn get(&self, index: usize, key: &[u8]) -> Option<&'a [u8]> {
+        if self.keys.get(index)? == key {
+            self.values.get(index)
+        } else {
+            None
+        }
+    }
+
+    fn iter(&self) -> impl ExactSizeIterator<Item = (&'a [u8], &'a [u8])> + '_ {
+        (0..self.len()).map(|idx| (
+            self.keys.get(idx).unwrap_or_default(),
+            self.values.get(idx).unwrap_or_default()
+        ))
+    }
+}
+
+impl<'a> BytesSeq<'a> {
+    fn load(len: u32, ends: &'a [u8], tag: u32, buf: &'a [u8]) -> Result<Self, LoadError> {
+        if usize::try_from(len).ok() != Some(ends.len() / 4) || !ends.len().is_multiple_of(4) {
+            return Err(LoadError::BadSectionLength { tag });
+        }
+
+        let mut start = 0;
+        for idx in 0..ends.len() / 4 {
+            let end = read_u32(ends, idx)
+                .and_then(|n| usize::try_from(n).ok())
+                .filter(|&end| start <= end && end <= buf.len())
+                .ok_or(LoadError::BadEnds { tag })?;
+            start = end;
+        }
+
+        Ok(BytesSeq { ends, buf })
+    }
+
+    fn len(&self) -> usize {
+        self.ends.len() / 4
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&'a [u8]> {
+        let start: usize = match index.checked_sub(1) {
+            Some(index) => read_u32(self.ends, index)?.try_into().ok()?,
+            None => 0
+        };
+        let end: usize = read_u32(self.ends, index)?.try_into().ok()?;
+        self.buf.get(start..end)
+    }
+}
+
+impl From<ContainerError> for LoadError {
+    fn from(err: ContainerError) -> Self {
+        LoadError::Container(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Container(err) => fmt::Display::fmt(err, f),
+            LoadError::MissingSection { tag } => write!(f, "missing section {:#x}", tag),
+            LoadError::BadSectionLength { tag } => write!(f, "bad length of section {:#x}", tag),
+            LoadError::BadEnds { tag } => write!(f, "bad ends in section {:#x}", tag),
+            LoadError::UnknownKind(kind) => write!(f, "unknown map kind: {}", kind),
+            LoadError::UnknownPilotWidth(width) => write!(f, "unknown pilot width: {}", width),
+            LoadError::KindMismatch { expected, found } =>
+                write!(f, "expected map kind {}, found {}", expected, found),
+            LoadError::BadIndex => f.write_str("bad index of map"),
+            LoadError::Unsorted => f.write_str("keys of tiny map are not sorted"),
+        }
+    }
+}
+
+impl core::error::Error for LoadError {}
+
+#[inline]
+fn read_u32(buf: &[u8], index: usize) -> Option<u32> {
+    let buf = buf.get(index.checked_mul(4)?..)?.get(..4)?;
+    Some(u32::from_le_bytes(buf.try_into().ok()?))
+}
@@ -1,9 +1,10 @@
 //! fork from <https://github.com/indexmap-rs/equivalent/blob/v1.0.2/src/lib.rs>
 
-use core::hash::Hash;
+use core::hash::{ Hash, Hasher };
 use core::cmp::Ordering;
 use core::borrow::Borrow;
 use crate::phf::HashOne;
+use crate::stable::StableHash;
 
 /// Key equivalence trait.
 ///
@@ -54,7 +55,10 @@ where
 }
 
 /// Hashable trait.
-pub trait Hashable<H: HashOne> {
+///
+/// `H` is the hash parameter of map,
+/// it is [`HashOne`] for [`Hash`] keys or [`Stable`](crate::stable::Stable) for [`StableHash`](crate::stable::StableHash) keys.
+pub trait Hashable<H> {
     fn hash(&self, seed: u64) -> u64;
 }
 
@@ -63,3 +67,146 @@ impl<T: Hash + ?Sized, H: HashOne> Hashable<H> for T {
         H::hash_one(seed, self)
     }
 }
+
+/// Query a byte-keyed map with `str`.
+///
+/// It hashes, compares and orders as `[u8]`,
+/// so the map must be built by hashing keys as `[u8]`.
+///
+/// ```rust,ignore
+/// MAP.get(&AsBytes("foo"))
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AsBytes<'a>(pub &'a str);
+
+/// Query a str-keyed map with `[u8]`.
+///
+/// It hashes, compares and orders as `str`,
+/// bytes that are not utf8 never match.
+///
+/// [`Hash`] writes `str` and `[u8]` differently, but [`StableHash`] does not,
+/// so with [`Stable`](crate::stable::Stable) the same map can be queried by both.
+#[derive(Clone, Copy, Debug)]
+pub struct AsStr<'a>(pub &'a [u8]);
+
+impl Equivalent<&[u8]> for AsBytes<'_> {
+    #[inline]
+    fn equivalent(&self, key: &&[u8]) -> bool {
+        self.0.as_bytes() == *key
+    }
+}
+
+impl Comparable<&[u8]> for AsBytes<'_> {
+    #[inline]
+    fn compare(&self, key: &&[u8]) -> Ordering {
+        self.0.as_bytes().cmp(key)
+    }
+}
+
+impl<H: HashOne> Hashable<H> for AsBytes<'_> {
+    #[inline]
+    fn hash(&self, seed: u64) -> u64 {
+        H::hash_one(seed, self.0.as_bytes())
+    }
+}
+
+impl StableHash for AsBytes<'_> {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.0.as_bytes().stable_hash(state);
+    }
+}
+
+impl Equivalent<&str> for AsStr<'_> {
+    #[inline]
+    fn equivalent(&self, key: &&str) -> bool {
+        self.0 == key.as_bytes()
+    }
+}
+
+impl Comparable<&str> for AsStr<'_> {
+    #[inline]
+    fn compare(&self, key: &&str) -> Ordering {
+        self.0.cmp(key.as_bytes())
+    }
+}
+
+impl<H: HashOne> Hashable<H> for AsStr<'_> {
+    #[inline]
+    fn hash(&self, seed: u64) -> u64 {
+        // `Hash` writes `str` and `[u8]` differently,
+        // the invalid utf8 hashes as bytes since it cannot be equivalent anyway.
+        match core::str::from_utf8(self.0) {
+            Ok(s) => H::hash_one(seed, s),
+            Err(_) => H::hash_one(seed, self.0)
+        }
+    }
+}
+
+impl StableHash for AsStr<'_> {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.0.stable_hash(state);
+    }
+}
+
+/// Query a map ignoring ASCII case, without allocating.
+///
+/// It compares and orders by ASCII lowercase bytes,
+/// and hashes the ASCII lowercase bytes with its own encoding,
+/// so the map must be built by hashing keys through this wrapper:
+///
+/// ```rust,ignore
+/// let mapout = MapBuilder::<String>::new()
+///     .set_normalize(&|k| k.to_ascii_lowercase())
+///     .set_hash(&|seed, k| Mix::hash_one(seed, AsciiCaseInsensitive(k)))
+///     .build(&keys)?;
+///
+/// MAP.get(&AsciiCaseInsensitive("Content-Type"))
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AsciiCaseInsensitive<Q>(pub Q);
+
+impl<Q: AsRef<[u8]>> AsciiCaseInsensitive<Q> {
+    fn write_lowercase(&self, mut f: impl FnMut(&[u8])) {
+        let mut buf = [0; 32];
+
+        for chunk in self.0.as_ref().chunks(buf.len()) {
+            let buf = &mut buf[..chunk.len()];
+            buf.copy_from_slice(chunk);
+            buf.make_ascii_lowercase();
+            f(buf);
+        }
+    }
+}
+
+impl<Q: AsRef<[u8]>> Hash for AsciiCaseInsensitive<Q> {
+    #[inline]
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        state.write_u64(self.0.as_ref().len() as u64);
+        self.write_lowercase(|buf| state.write(buf));
+    }
+}
+
+impl<Q: AsRef<[u8]>> StableHash for AsciiCaseInsensitive<Q> {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.0.as_ref().len().stable_hash(state);
+        self.write_lowercase(|buf| state.write(buf));
+    }
+}
+
+impl<Q: AsRef<[u8]>, K: AsRef<[u8]> + ?Sized> Equivalent<K> for AsciiCaseInsensitive<Q> {
+    #[inline]
+    fn equivalent(&self, key: &K) -> bool {
+        self.0.as_ref().eq_ignore_ascii_case(key.as_ref())
+    }
+}
+
+impl<Q: AsRef<[u8]>, K: AsRef<[u8]> + ?Sized> Comparable<K> for AsciiCaseInsensitive<Q> {
+    #[inline]
+    fn compare(&self, key: &K) -> Ordering {
+        let key = key.as_ref().iter().map(u8::to_ascii_lowercase);
+        self.0.as_ref().iter().map(u8::to_ascii_lowercase).cmp(key)
+    }
+}
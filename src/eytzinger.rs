@@ -0,0 +1,118 @@
+//! Eytzinger layout
+//!
+//! Node `k` (1-based) has children `2k` and `2k + 1`, it is stored at index `k - 1`.
+//! Node `0` means none.
+
+/// Returns the first node that `is_less` returns `false`.
+#[inline]
+pub(crate) fn search(n: usize, mut is_less: impl FnMut(usize) -> bool) -> usize {
+    let mut k = 1;
+
+    while k <= n {
+        k = 2 * k + usize::from(is_less(k));
+    }
+
+    // Go back to the last node that we turned left.
+    k >> (k.trailing_ones() + 1)
+}
+
+pub(crate) fn first(n: usize) -> usize {
+    let mut k = usize::from(n > 0);
+
+    while 2 * k <= n && k != 0 {
+        k *= 2;
+    }
+
+    k
+}
+
+pub(crate) fn last(n: usize) -> usize {
+    let mut k = usize::from(n > 0);
+
+    while 2 * k < n && k != 0 {
+        k = 2 * k + 1;
+    }
+
+    k
+}
+
+/// In-order successor
+pub(crate) fn next(k: usize, n: usize) -> usize {
+    if 2 * k < n {
+        // leftmost node of right subtree
+        let mut k = 2 * k + 1;
+
+        while 2 * k <= n {
+            k *= 2;
+        }
+
+        k
+    } else {
+        // parent of the first ancestor that is left child
+        let mut k = k;
+
+        while k & 1 == 1 {
+            k >>= 1;
+        }
+
+        k >> 1
+    }
+}
+
+/// In-order predecessor
+pub(crate) fn prev(k: usize, n: usize) -> usize {
+    if 2 * k <= n {
+        // rightmost node of left subtree
+        let mut k = 2 * k;
+
+        while 2 * k < n {
+            k = 2 * k + 1;
+        }
+
+        k
+    } else {
+        // parent of the first ancestor that is right child
+        let mut k = k;
+
+        while k != 0 && k & 1 == 0 {
+            k >>= 1;
+        }
+
+        k >> 1
+    }
+}
+
+fn subtree_len(k: usize, n: usize) -> usize {
+    let mut lo = k;
+    let mut hi = k;
+    let mut len = 0;
+
+    while lo <= n {
+        len += hi.min(n) - lo + 1;
+        lo *= 2;
+        hi = 2 * hi + 1;
+    }
+
+    len
+}
+
+/// In-order position of node, `n` if none.
+pub(crate) fn rank(k: usize, n: usize) -> usize {
+    if k == 0 {
+        return n;
+    }
+
+    let mut rank = subtree_len(2 * k, n);
+    let mut k = k;
+
+    while k > 1 {
+        if k & 1 == 1 {
+            // parent and left sibling are in front of the right child.
+            rank += subtree_len(k - 1, n) + 1;
+        }
+
+        k >>= 1;
+    }
+
+    rank
+}
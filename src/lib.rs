@@ -6,13 +6,17 @@ pub mod builder;
 pub mod phf;
 
 mod macros;
+mod eytzinger;
 pub mod equivalent;
 pub mod seq;
 pub mod store;
+pub mod dynamic;
+pub mod container;
 pub mod aligned;
+pub mod stable;
 
 use core::marker::PhantomData;
-use phf::HashOne;
+use core::ops::{ Bound, RangeBounds, RangeInclusive };
 use equivalent::{ Equivalent, Comparable, Hashable };
 
 
@@ -47,6 +51,54 @@ impl<M: store::Searchable> TinyMap<M> {
         M::get_value(idx)
     }
 
+    /// Returns the first entry whose key is not less than query.
+    pub fn lower_bound<Q>(&self, key: &Q)
+        -> Option<(M::Key, M::Value)>
+    where
+        Q: Comparable<M::Key> + ?Sized
+    {
+        self.range::<Q, _>((Bound::Included(key), Bound::Unbounded)).next()
+    }
+
+    /// Returns the first entry whose key is greater than query.
+    pub fn upper_bound<Q>(&self, key: &Q)
+        -> Option<(M::Key, M::Value)>
+    where
+        Q: Comparable<M::Key> + ?Sized
+    {
+        self.range::<Q, _>((Bound::Excluded(key), Bound::Unbounded)).next()
+    }
+
+    pub fn first(&self) -> Option<(M::Key, M::Value)> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<(M::Key, M::Value)> {
+        self.iter().next_back()
+    }
+
+    /// Iterates over the entries in range, in ascending order.
+    ///
+    /// The greatest key less than or equal to query can be found by `map.range(..=query).next_back()`.
+    pub fn range<Q, R>(&self, range: R) -> store::MapIter<'_, M>
+    where
+        Q: Comparable<M::Key> + ?Sized,
+        R: RangeBounds<Q>
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => M::lower_bound(key),
+            Bound::Excluded(key) => M::upper_bound(key),
+            Bound::Unbounded => 0
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => M::upper_bound(key),
+            Bound::Excluded(key) => M::lower_bound(key),
+            Bound::Unbounded => M::LEN
+        };
+
+        store::MapIter::range(start, end.max(start))
+    }
+
     pub const fn iter(&self) -> store::MapIter<'_, M> {
         store::MapIter::new()
     }
@@ -63,7 +115,6 @@ pub struct SmallMap<D, H> {
 impl<D, H> SmallMap<D, H>
 where
     D: store::MapStore,
-    H: HashOne,
 {
     #[doc(hidden)]
     pub const fn new(seed: u64) -> Self {
@@ -82,15 +133,11 @@ where
     }
     
     #[inline]
-    fn inner_get<Q>(&self, key: &Q) -> usize
-    where
-        Q: Hashable<H> + ?Sized,
-    {
-        let size: u32 = D::LEN.try_into().unwrap();
+    fn inner_get(&self, hash: u64) -> Option<usize> {
+        let size: u32 = D::LEN.try_into().ok()?;
 
-        let hash = key.hash(self.seed);
         let index = fast_reduct32(high(hash) ^ low(hash), size);
-        index.try_into().unwrap()
+        index.try_into().ok()
     }
 
     pub fn get<Q>(&self, key: &Q) -> Option<D::Value>
@@ -101,12 +148,56 @@ where
             return None;
         }
         
-        let index = self.inner_get(key);
-        if key.equivalent(&D::get_key(index)?) {
-            D::get_value(index)
-        } else {
-            None
+        let hash = key.hash(self.seed);
+        let index = self.inner_get(hash)?;
+        check_and_get::<D, Q>(index, hash, key)
+    }
+
+    /// Returns the index of key without verifying the key.
+    ///
+    /// If key is not in map, an arbitrary index may be returned.
+    /// Only the fingerprint is checked, if the map has one.
+    pub fn index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hashable<H> + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
         }
+
+        let hash = key.hash(self.seed);
+        let index = self.inner_get(hash)?;
+        D::check_fingerprint(index, hash).then_some(index)
+    }
+
+    /// Batched version of [`SmallMap::get_batch`] with fixed size.
+    pub fn get_many<Q, const N: usize>(&self, keys: [&Q; N]) -> [Option<D::Value>; N]
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        let mut output = core::array::from_fn(|_| None);
+        self.get_batch(&keys, &mut output);
+        output
+    }
+
+    /// Looks up a batch of keys, the results are written to `output`.
+    ///
+    /// The keys are hashed first, then compared,
+    /// see [`MediumMap::get_batch`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lengths of `keys` and `output` are not equal.
+    pub fn get_batch<Q>(&self, keys: &[&Q], output: &mut [Option<D::Value>])
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        get_batch::<D, Q>(
+            keys,
+            output,
+            |key| key.hash(self.seed),
+            |hash| if self.is_empty() { None } else { self.inner_get(hash) }
+        )
     }
 
     pub const fn iter(&self) -> store::MapIter<'_, D> {
@@ -136,10 +227,10 @@ impl<
     H,
 > MediumMap<P, R, D, H>
 where
-    P: store::AccessSeq<Item = u8>,
+    P: store::AccessSeq,
+    P::Item: phf::Pilot,
     R: store::AccessSeq<Item = u32>,
     D: store::MapStore,
-    H: HashOne
 {
     #[doc(hidden)]
     pub const fn new(seed: u64) -> Self {
@@ -158,22 +249,18 @@ where
     }
 
     #[inline]    
-    fn inner_get<Q>(&self, key: &Q) -> usize
-    where
-        Q: Hashable<H> + ?Sized,
-    {
-        let pilots_len: u32 = P::LEN.try_into().unwrap();
-        let slots_len: u32 = (D::LEN + R::LEN).try_into().unwrap();
+    fn inner_get(&self, hash: u64) -> Option<usize> {
+        let pilots_len: u32 = P::LEN.try_into().ok()?;
+        let slots_len: u32 = (D::LEN + R::LEN).try_into().ok()?;
 
-        let hash = key.hash(self.seed);
-        let bucket: usize = fast_reduct32(low(hash), pilots_len).try_into().unwrap();
-        let pilot = P::index(bucket).unwrap();
+        let bucket: usize = fast_reduct32(low(hash), pilots_len).try_into().ok()?;
+        let pilot = P::index(bucket)?;
         let pilot_hash = phf::hash_pilot(self.seed, pilot);
 
         fast_reduct32(
             high(hash) ^ high(pilot_hash) ^ low(pilot_hash),
             slots_len
-        ).try_into().unwrap()
+        ).try_into().ok()
     }
 
     pub fn get<Q>(&self, key: &Q) -> Option<D::Value>
@@ -182,36 +269,244 @@ where
     {
         #[cold]
         #[inline(always)]
-        fn remap_and_index<R, D, Q>(index: usize, key: &Q)
+        fn remap_and_index<R, D, Q>(index: usize, hash: u64, key: &Q)
         -> Option<D::Value>
         where
             R: store::AccessSeq<Item = u32>,
             D: store::MapStore,
             Q: Equivalent<D::Key> + ?Sized,
         {
-            let index: usize = R::index(index - D::LEN).unwrap().try_into().unwrap();
-            if key.equivalent(&D::get_key(index)?) {
-                D::get_value(index)
-            } else {
-                None
-            }
+            let index: usize = R::index(index - D::LEN)?.try_into().ok()?;
+            check_and_get::<D, Q>(index, hash, key)
         }
                 
         if self.is_empty() {
             return None;
         }
         
-        let index = self.inner_get(key);
-
-        if index < D::LEN {
-            if key.equivalent(&D::get_key(index)?) {
-                D::get_value(index)
-            } else {
-                None
-            }
+        let hash = key.hash(self.seed);
+        let index = self.inner_get(hash)?;
+
+        // Without remap table, the branch is removed at compile time.
+        if R::LEN == 0 || index < D::LEN {
+            check_and_get::<D, Q>(index, hash, key)
+        } else {
+            remap_and_index::<R, D, Q>(index, hash, key)
+        }
+    }
+
+    /// Returns the index of key without verifying the key.
+    ///
+    /// If key is not in map, an arbitrary index may be returned.
+    /// Only the fingerprint is checked, if the map has one.
+    pub fn index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hashable<H> + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+
+        let hash = key.hash(self.seed);
+        let index = self.remap_get(hash)?;
+        D::check_fingerprint(index, hash).then_some(index)
+    }
+
+    /// Batched version of [`MediumMap::get_batch`] with fixed size.
+    pub fn get_many<Q, const N: usize>(&self, keys: [&Q; N]) -> [Option<D::Value>; N]
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        let mut output = core::array::from_fn(|_| None);
+        self.get_batch(&keys, &mut output);
+        output
+    }
+
+    /// Looks up a batch of keys, the results are written to `output`.
+    ///
+    /// The keys are processed in passes, hash, pilot and slot, then key,
+    /// so the memory accesses of different keys are independent and overlap with each other.
+    /// This is more throughput than calling [`MediumMap::get`] in a loop.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lengths of `keys` and `output` are not equal.
+    pub fn get_batch<Q>(&self, keys: &[&Q], output: &mut [Option<D::Value>])
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        get_batch::<D, Q>(
+            keys,
+            output,
+            |key| key.hash(self.seed),
+            |hash| if self.is_empty() { None } else { self.remap_get(hash) }
+        )
+    }
+
+    #[inline]
+    fn remap_get(&self, hash: u64) -> Option<usize> {
+        let index = self.inner_get(hash)?;
+
+        if R::LEN == 0 || index < D::LEN {
+            Some(index)
+        } else {
+            R::index(index - D::LEN)?.try_into().ok()
+        }
+    }
+
+    pub const fn iter(&self) -> store::MapIter<'_, D> {
+        store::MapIter::new()
+    }
+}
+
+/// Large map
+///
+/// 10M..
+pub struct LargeMap<
+    O,
+    S,
+    T,
+    P,
+    R,
+    D,
+    H,
+> {
+    seed: u64,
+    _phantom: PhantomData<(
+        O, S, T, P, R, D, H
+    )>
+}
+
+impl<
+    O,
+    S,
+    T,
+    P,
+    R,
+    D,
+    H,
+> LargeMap<O, S, T, P, R, D, H>
+where
+    O: store::AccessSeq<Item = u32>,
+    S: store::AccessSeq<Item = u32>,
+    T: store::AccessSeq<Item = u32>,
+    P: store::AccessSeq,
+    P::Item: phf::Pilot,
+    R: store::AccessSeq<Item = u32>,
+    D: store::MapStore,
+{
+    #[doc(hidden)]
+    pub const fn new(seed: u64) -> Self {
+        LargeMap {
+            seed,
+            _phantom: PhantomData
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        D::LEN
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    fn inner_get(&self, hash: u64) -> Option<usize> {
+        // Each shard is a medium map of its own,
+        // `O` and `S` are the prefix sums of the keys and slots of the shards,
+        // `T` is the retry count of the pilot seed of each shard.
+        let shards_len = O::LEN.checked_sub(1).filter(|&n| n > 0)?;
+        let buckets_len: u32 = (P::LEN / shards_len).try_into().ok()?;
+
+        let shard: usize = shard_index(hash, shards_len.try_into().ok()?)
+            .try_into()
+            .ok()?;
+        let keys_start: usize = O::index(shard)?.try_into().ok()?;
+        let keys_end: usize = O::index(shard + 1)?.try_into().ok()?;
+        let slots_start: usize = S::index(shard)?.try_into().ok()?;
+        let slots_end: usize = S::index(shard + 1)?.try_into().ok()?;
+        let keys_len = keys_end.checked_sub(keys_start)?;
+
+        if keys_len == 0 {
+            return None;
+        }
+
+        let bucket: usize = fast_reduct32(low(hash), buckets_len).try_into().ok()?;
+        let pilot = P::index(shard * buckets_len as usize + bucket)?;
+        let pilot_hash = phf::hash_pilot(shard_seed(self.seed, T::index(shard)?), pilot);
+
+        let slots_len: u32 = slots_end.checked_sub(slots_start)?.try_into().ok()?;
+        let index: usize = fast_reduct32(
+            high(hash) ^ high(pilot_hash) ^ low(pilot_hash),
+            slots_len
+        ).try_into().ok()?;
+
+        if index < keys_len {
+            Some(keys_start + index)
         } else {
-            remap_and_index::<R, D, Q>(index, key)
+            let remap = slots_start.checked_sub(keys_start)? + (index - keys_len);
+            R::index(remap)?.try_into().ok()
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<D::Value>
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+
+        let hash = key.hash(self.seed);
+        let index = self.inner_get(hash)?;
+        check_and_get::<D, Q>(index, hash, key)
+    }
+
+    /// Returns the index of key without verifying the key.
+    ///
+    /// If key is not in map, an arbitrary index may be returned.
+    /// Only the fingerprint is checked, if the map has one.
+    pub fn index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hashable<H> + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
         }
+
+        let hash = key.hash(self.seed);
+        let index = self.inner_get(hash)?;
+        D::check_fingerprint(index, hash).then_some(index)
+    }
+
+    /// Batched version of [`LargeMap::get_batch`] with fixed size.
+    pub fn get_many<Q, const N: usize>(&self, keys: [&Q; N]) -> [Option<D::Value>; N]
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        let mut output = core::array::from_fn(|_| None);
+        self.get_batch(&keys, &mut output);
+        output
+    }
+
+    /// Looks up a batch of keys, the results are written to `output`.
+    ///
+    /// See [`MediumMap::get_batch`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lengths of `keys` and `output` are not equal.
+    pub fn get_batch<Q>(&self, keys: &[&Q], output: &mut [Option<D::Value>])
+    where
+        Q: Equivalent<D::Key> + Hashable<H> + ?Sized,
+    {
+        get_batch::<D, Q>(
+            keys,
+            output,
+            |key| key.hash(self.seed),
+            |hash| if self.is_empty() { None } else { self.inner_get(hash) }
+        )
     }
 
     pub const fn iter(&self) -> store::MapIter<'_, D> {
@@ -219,6 +514,462 @@ where
     }
 }
 
+/// Tiny set
+///
+/// 0..16
+pub struct TinySet<K> {
+    map: TinyMap<K>
+}
+
+impl<K: store::Searchable + store::AccessSeq> TinySet<K> {
+    #[doc(hidden)]
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> TinySet<K> {
+        TinySet { map: TinyMap::new() }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Comparable<K::Key> + ?Sized
+    {
+        self.map.get(key).is_some()
+    }
+
+    pub const fn iter(&self) -> store::SetIter<'_, K> {
+        store::SetIter::new()
+    }
+}
+
+/// Small set
+///
+/// 0..12
+pub struct SmallSet<K, H> {
+    map: SmallMap<K, H>
+}
+
+impl<K, H> SmallSet<K, H>
+where
+    K: store::MapStore,
+{
+    #[doc(hidden)]
+    pub const fn new(seed: u64) -> Self {
+        SmallSet { map: SmallMap::new(seed) }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Equivalent<K::Key> + Hashable<H> + ?Sized,
+    {
+        self.map.get(key).is_some()
+    }
+
+    pub const fn iter(&self) -> store::SetIter<'_, K> {
+        store::SetIter::new()
+    }
+}
+
+/// Medium set
+///
+/// 1024..10M
+pub struct MediumSet<P, R, K, H> {
+    map: MediumMap<P, R, K, H>
+}
+
+impl<P, R, K, H> MediumSet<P, R, K, H>
+where
+    P: store::AccessSeq,
+    P::Item: phf::Pilot,
+    R: store::AccessSeq<Item = u32>,
+    K: store::MapStore,
+{
+    #[doc(hidden)]
+    pub const fn new(seed: u64) -> Self {
+        MediumSet { map: MediumMap::new(seed) }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Equivalent<K::Key> + Hashable<H> + ?Sized,
+    {
+        self.map.get(key).is_some()
+    }
+
+    pub const fn iter(&self) -> store::SetIter<'_, K> {
+        store::SetIter::new()
+    }
+}
+
+/// Large set
+///
+/// 10M..
+pub struct LargeSet<O, S, T, P, R, K, H> {
+    map: LargeMap<O, S, T, P, R, K, H>
+}
+
+impl<O, S, T, P, R, K, H> LargeSet<O, S, T, P, R, K, H>
+where
+    O: store::AccessSeq<Item = u32>,
+    S: store::AccessSeq<Item = u32>,
+    T: store::AccessSeq<Item = u32>,
+    P: store::AccessSeq,
+    P::Item: phf::Pilot,
+    R: store::AccessSeq<Item = u32>,
+    K: store::MapStore,
+{
+    #[doc(hidden)]
+    pub const fn new(seed: u64) -> Self {
+        LargeSet { map: LargeMap::new(seed) }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Equivalent<K::Key> + Hashable<H> + ?Sized,
+    {
+        self.map.get(key).is_some()
+    }
+
+    pub const fn iter(&self) -> store::SetIter<'_, K> {
+        store::SetIter::new()
+    }
+}
+
+/// Ordered map
+///
+/// Keys are stored in eytzinger layout, this is suitable for ordered query of any size.
+pub struct OrderedMap<D> {
+    _phantom: PhantomData<D>
+}
+
+impl<D: store::MapStore> OrderedMap<D> {
+    #[doc(hidden)]
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> OrderedMap<D> {
+        OrderedMap { _phantom: PhantomData }
+    }
+
+    pub const fn len(&self) -> usize {
+        D::LEN
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Node of first key that is not less than query.
+    #[inline]
+    fn lower_bound_node<Q>(key: &Q) -> usize
+    where
+        Q: Comparable<D::Key> + ?Sized
+    {
+        eytzinger::search(D::LEN, |k| D::get_key(k - 1)
+            .is_some_and(|k| key.compare(&k).is_gt())
+        )
+    }
+
+    /// Node of first key that is greater than query.
+    #[inline]
+    fn upper_bound_node<Q>(key: &Q) -> usize
+    where
+        Q: Comparable<D::Key> + ?Sized
+    {
+        eytzinger::search(D::LEN, |k| D::get_key(k - 1)
+            .is_some_and(|k| key.compare(&k).is_ge())
+        )
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<D::Value>
+    where
+        Q: Comparable<D::Key> + ?Sized
+    {
+        let k = Self::lower_bound_node(key);
+        let index = k.checked_sub(1)?;
+
+        if key.compare(&D::get_key(index)?).is_eq() {
+            D::get_value(index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the first entry whose key is not less than query.
+    pub fn lower_bound<Q>(&self, key: &Q)
+        -> Option<(D::Key, D::Value)>
+    where
+        Q: Comparable<D::Key> + ?Sized
+    {
+        self.range::<Q, _>((Bound::Included(key), Bound::Unbounded)).next()
+    }
+
+    /// Returns the first entry whose key is greater than query.
+    pub fn upper_bound<Q>(&self, key: &Q)
+        -> Option<(D::Key, D::Value)>
+    where
+        Q: Comparable<D::Key> + ?Sized
+    {
+        self.range::<Q, _>((Bound::Excluded(key), Bound::Unbounded)).next()
+    }
+
+    pub fn first(&self) -> Option<(D::Key, D::Value)> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<(D::Key, D::Value)> {
+        self.iter().next_back()
+    }
+
+    /// Iterates over the entries in range, in ascending order.
+    ///
+    /// The greatest key less than or equal to query can be found by `map.range(..=query).next_back()`.
+    pub fn range<Q, R>(&self, range: R) -> store::OrderedIter<'_, D>
+    where
+        Q: Comparable<D::Key> + ?Sized,
+        R: RangeBounds<Q>
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => Self::lower_bound_node(key),
+            Bound::Excluded(key) => Self::upper_bound_node(key),
+            Bound::Unbounded => eytzinger::first(D::LEN)
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => Self::upper_bound_node(key),
+            Bound::Excluded(key) => Self::lower_bound_node(key),
+            Bound::Unbounded => 0
+        };
+
+        store::OrderedIter::range(start, end)
+    }
+
+    pub fn iter(&self) -> store::OrderedIter<'_, D> {
+        store::OrderedIter::range(eytzinger::first(D::LEN), 0)
+    }
+}
+
+/// Ordered set
+///
+/// Keys are stored in eytzinger layout, this is suitable for ordered query of any size.
+pub struct OrderedSet<K> {
+    map: OrderedMap<K>
+}
+
+impl<K: store::AccessSeq> OrderedSet<K> {
+    #[doc(hidden)]
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> OrderedSet<K> {
+        OrderedSet { map: OrderedMap::new() }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Comparable<K::Item> + ?Sized
+    {
+        self.map.get(key).is_some()
+    }
+
+    /// Iterates over the keys in range, in ascending order.
+    pub fn range<Q, R>(&self, range: R)
+        -> impl DoubleEndedIterator<Item = K::Item> + ExactSizeIterator + '_
+    where
+        Q: Comparable<K::Item> + ?Sized,
+        R: RangeBounds<Q>
+    {
+        self.map.range(range).map(|(k, _)| k)
+    }
+
+    pub fn iter(&self)
+        -> impl DoubleEndedIterator<Item = K::Item> + ExactSizeIterator + '_
+    {
+        self.map.iter().map(|(k, _)| k)
+    }
+}
+
+/// Range map
+///
+/// Maps non-overlapping `u32` ranges to values,
+/// `S` and `E` are the start and inclusive end of each range, in ascending order.
+pub struct RangeMap<S, E, V> {
+    _phantom: PhantomData<(S, E, V)>
+}
+
+impl<S, E, V> RangeMap<S, E, V>
+where
+    S: store::AccessSeq<Item = u32>,
+    E: store::AccessSeq<Item = u32>,
+    V: store::AccessSeq,
+{
+    #[doc(hidden)]
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> RangeMap<S, E, V> {
+        RangeMap { _phantom: PhantomData }
+    }
+
+    pub const fn len(&self) -> usize {
+        S::LEN
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn search(&self, key: u32) -> Option<usize> {
+        // the last range whose start is not greater than key
+        let idx = store::partition_point(S::LEN, |idx| S::index(idx)
+            .is_some_and(|start| start <= key)
+        );
+        let idx = idx.checked_sub(1)?;
+
+        if key <= E::index(idx)? {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of the range containing key.
+    pub fn get(&self, key: u32) -> Option<V::Item> {
+        let idx = self.search(key)?;
+        V::index(idx)
+    }
+
+    /// Returns the range containing key, and its value.
+    pub fn get_range_value(&self, key: u32) -> Option<(RangeInclusive<u32>, V::Item)> {
+        let idx = self.search(key)?;
+        self.entry(idx)
+    }
+
+    pub fn contains(&self, key: u32) -> bool {
+        self.search(key).is_some()
+    }
+
+    fn entry(&self, idx: usize) -> Option<(RangeInclusive<u32>, V::Item)> {
+        let start = S::index(idx)?;
+        let end = E::index(idx)?;
+        let value = V::index(idx)?;
+        Some((start..=end, value))
+    }
+
+    pub fn iter(&self)
+        -> impl DoubleEndedIterator<Item = (RangeInclusive<u32>, V::Item)> + ExactSizeIterator + '_
+    {
+        (0..S::LEN).map(|idx| self.entry(idx).unwrap())
+    }
+}
+
+#[inline(always)]
+fn check_and_get<D, Q>(index: usize, hash: u64, key: &Q) -> Option<D::Value>
+where
+    D: store::MapStore,
+    Q: Equivalent<D::Key> + ?Sized,
+{
+    // The fingerprint is checked first, so most misses don't need to load the key.
+    if D::check_fingerprint(index, hash) && key.equivalent(&D::get_key(index)?) {
+        D::get_value(index)
+    } else {
+        None
+    }
+}
+
+/// Batched lookup
+///
+/// Each pass runs over the whole batch before the next one,
+/// so that the loads of different keys don't depend on each other
+/// and the CPU can keep several of them in flight.
+///
+/// There is no explicit prefetch, because prefetch intrinsics require unsafe code.
+#[inline(always)]
+fn get_batch<D, Q>(
+    keys: &[&Q],
+    output: &mut [Option<D::Value>],
+    hash: impl Fn(&Q) -> u64,
+    index: impl Fn(u64) -> Option<usize>,
+)
+where
+    D: store::MapStore,
+    Q: Equivalent<D::Key> + ?Sized,
+{
+    const BATCH: usize = 16;
+
+    assert_eq!(keys.len(), output.len());
+
+    for (keys, output) in keys.chunks(BATCH).zip(output.chunks_mut(BATCH)) {
+        let mut hashes = [0; BATCH];
+        let mut indexes = [None; BATCH];
+
+        for (h, key) in hashes.iter_mut().zip(keys) {
+            *h = hash(key);
+        }
+
+        for (idx, &h) in indexes.iter_mut().zip(&hashes[..keys.len()]) {
+            *idx = index(h);
+        }
+
+        for (((out, key), &h), &idx) in output.iter_mut().zip(keys).zip(&hashes).zip(&indexes) {
+            *out = idx.and_then(|idx| check_and_get::<D, Q>(idx, h, key));
+        }
+    }
+}
+
+#[inline]
+fn shard_index(hash: u64, shards_len: u32) -> u32 {
+    // golden ratio
+    const C: u64 = 0x9e3779b97f4a7c15;
+
+    // The bucket and slot are taken from the high bits of `low` and `high`,
+    // so mix it once more to get a shard independent of them.
+    fast_reduct32(high(hash.wrapping_mul(C)), shards_len)
+}
+
+/// Pilot seed of a large map shard
+///
+/// A shard is retried with a new pilot seed if no pilots were found,
+/// `retry` is `0` for most shards, so the pilot seed is the map seed.
+#[inline]
+fn shard_seed(seed: u64, retry: u32) -> u64 {
+    // golden ratio
+    const C: u64 = 0x9e3779b97f4a7c15;
+
+    seed ^ u64::from(retry).wrapping_mul(C)
+}
+
 // https://lemire.me/blog/2016/06/27/a-fast-alternative-to-the-modulo-reduction/
 #[inline]
 fn fast_reduct32(x: u32, limit: u32) -> u32 {
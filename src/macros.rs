@@ -40,6 +40,22 @@ macro_rules! define {
                 let values = $name.as_slice();
                 values.binary_search_by(|k| query.compare(k).reverse()).ok()
             }
+
+            fn lower_bound<Q>(query: &Q) -> usize
+            where
+                Q: $crate::equivalent::Comparable<Self::Key> + ?Sized
+            {
+                let values = $name.as_slice();
+                values.partition_point(|k| query.compare(k).is_gt())
+            }
+
+            fn upper_bound<Q>(query: &Q) -> usize
+            where
+                Q: $crate::equivalent::Comparable<Self::Key> + ?Sized
+            {
+                let values = $name.as_slice();
+                values.partition_point(|k| query.compare(k).is_ge())
+            }
         }        
     };
     ( $vis:vis const $name:ident: &[$unit:ty; $n:expr] = $v:expr ) => {
@@ -6,11 +6,26 @@ pub trait HashOne {
     fn hash_one<T: Hash>(k: u64, v: T) -> u64;
 }
 
-pub(crate) fn hash_pilot(k: u64, pilot: u8) -> u64 {
+/// Wide hash without seed
+///
+/// Used with [`Remix`] to hash key only once in construction.
+pub trait HashWide {
+    fn hash_wide<T: Hash>(v: T) -> u128;
+}
+
+/// Pilot of medium and large map
+///
+/// A `u8` pilot hashes the same as the `u16` pilot of same value.
+pub trait Pilot: Copy + Into<u64> {}
+
+impl Pilot for u8 {}
+impl Pilot for u16 {}
+
+pub(crate) fn hash_pilot(k: u64, pilot: impl Pilot) -> u64 {
     const C: u64 = 0x517cc1b727220a95;
 
     // fxhash
-    C.wrapping_mul(k ^ u64::from(pilot))
+    C.wrapping_mul(k ^ pilot.into())
 }
 
 #[derive(Default)]
@@ -24,3 +39,289 @@ impl<H: Hasher + Default> HashOne for U64Hasher<H> {
         h.finish()
     }
 }
+
+/// Derive seeded hash from wide hash
+///
+/// The seeded hash is computed by [`remix`], so construction can hash the key only once
+/// and derive the hash of every seed from it.
+#[derive(Default)]
+pub struct Remix<H: HashWide>(PhantomData<H>);
+
+impl<H: HashWide> HashOne for Remix<H> {
+    #[inline]
+    fn hash_one<T: Hash>(k: u64, v: T) -> u64 {
+        remix(k, H::hash_wide(v))
+    }
+}
+
+/// Derive the hash of seed `k` from wide hash
+#[inline]
+pub fn remix(k: u64, hash: u128) -> u64 {
+    const C: u64 = 0x517cc1b727220a95;
+
+    folded_multiply((hash as u64) ^ k, ((hash >> 64) as u64) ^ C)
+}
+
+/// foldhash folded multiply
+#[inline(always)]
+fn folded_multiply(x: u64, y: u64) -> u64 {
+    let full = u128::from(x) * u128::from(y);
+    (full as u64) ^ ((full >> 64) as u64)
+}
+
+/// Wide hash composed of two seeded hashes
+#[derive(Default)]
+pub struct Wide<H: HashOne>(PhantomData<H>);
+
+impl<H: HashOne> HashWide for Wide<H> {
+    fn hash_wide<T: Hash>(v: T) -> u128 {
+        let lo = H::hash_one(0x243f6a8885a308d3, &v);
+        let hi = H::hash_one(0x13198a2e03707344, &v);
+        u128::from(lo) | (u128::from(hi) << 64)
+    }
+}
+
+macro_rules! portable_hasher {
+    ( $name:ident, $hasher:ident ) => {
+        #[derive(Default)]
+        pub struct $name;
+
+        impl HashOne for $name {
+            #[inline]
+            fn hash_one<T: Hash>(k: u64, v: T) -> u64 {
+                let mut hasher = $hasher::with_seed(k);
+                v.hash(&mut hasher);
+                hasher.finish()
+            }
+        }
+
+        impl Default for $hasher {
+            fn default() -> Self {
+                $hasher::with_seed(0)
+            }
+        }
+    };
+    // Scalar integers are hashed by value instead of native bytes,
+    // and `usize` is hashed as `u64`, so the hash is the same on all targets.
+    //
+    // Integer slices and arrays are written by `Hash::hash_slice` as native bytes,
+    // which a `Hasher` cannot tell from a byte string, so they are not portable.
+    ( @write ) => {
+        #[inline]
+        fn write_u8(&mut self, i: u8) {
+            self.write_u64(i.into());
+        }
+
+        #[inline]
+        fn write_u16(&mut self, i: u16) {
+            self.write_u64(i.into());
+        }
+
+        #[inline]
+        fn write_u32(&mut self, i: u32) {
+            self.write_u64(i.into());
+        }
+
+        #[inline]
+        fn write_u128(&mut self, i: u128) {
+            self.write_u64(i as u64);
+            self.write_u64((i >> 64) as u64);
+        }
+
+        #[inline]
+        fn write_usize(&mut self, i: usize) {
+            self.write_u64(i as u64);
+        }
+
+        #[inline]
+        fn write_isize(&mut self, i: isize) {
+            self.write_u64(i as i64 as u64);
+        }
+    };
+}
+
+/// Reads up to 8 bytes as little-endian u64, zero padded.
+#[inline(always)]
+fn read_partial(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    let len = bytes.len().min(8);
+    buf[..len].copy_from_slice(&bytes[..len]);
+    u64::from_le_bytes(buf)
+}
+
+/// Calls `f` with each 16 bytes of `bytes` as two little-endian u64,
+/// the last one is zero padded.
+#[inline(always)]
+fn for_each_pair(bytes: &[u8], mut f: impl FnMut(u64, u64)) {
+    let mut chunks = bytes.chunks_exact(16);
+    for chunk in chunks.by_ref() {
+        let (a, b) = chunk.split_at(8);
+        f(read_partial(a), read_partial(b));
+    }
+
+    let rem = chunks.remainder();
+    if !rem.is_empty() {
+        let (a, b) = rem.split_at(rem.len().min(8));
+        f(read_partial(a), read_partial(b));
+    }
+}
+
+portable_hasher!(Fx, FxHasher);
+
+/// Portable FxHash
+///
+/// It is the FxHasher of rustc-hash 1.x on 64-bit little-endian targets,
+/// with the state initialized by seed.
+/// Fastest, but it is weak for keys with common patterns.
+///
+/// # Portability
+///
+/// Bytes, `str`, scalar integers, `usize` and lengths hash the same on all targets.
+/// Integer slices and arrays, such as `[u32]`, `[u16; N]` and `Vec<u64>`,
+/// are hashed as native-endian bytes by [`Hash`], so they differ between
+/// little and big-endian targets, and `[usize]` also differs between 32 and 64-bit.
+/// Hash such keys by [`StableHash`](crate::stable::StableHash) instead.
+pub struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    pub const fn with_seed(seed: u64) -> Self {
+        FxHasher { hash: seed }
+    }
+
+    #[inline(always)]
+    fn add_to_hash(&mut self, i: u64) {
+        const C: u64 = 0x517cc1b727220a95;
+
+        self.hash = (self.hash.rotate_left(5) ^ i).wrapping_mul(C);
+    }
+}
+
+impl Hasher for FxHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in chunks.by_ref() {
+            self.add_to_hash(read_partial(chunk));
+        }
+
+        let mut rem = chunks.remainder();
+        if let Some((n, next)) = rem.split_first_chunk::<4>() {
+            self.add_to_hash(u32::from_le_bytes(*n).into());
+            rem = next;
+        }
+        if let Some((n, next)) = rem.split_first_chunk::<2>() {
+            self.add_to_hash(u16::from_le_bytes(*n).into());
+            rem = next;
+        }
+        if let Some(&n) = rem.first() {
+            self.add_to_hash(n.into());
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    portable_hasher!(@write);
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+portable_hasher!(Mix, MixHasher);
+
+/// Portable multiply-mix hash
+///
+/// Each write is mixed by a full 64x64-bit folded multiply,
+/// bytes are read as little-endian u64 pairs with the length mixed in.
+/// Slower than [`FxHasher`], but has good quality for any keys.
+/// It is not compatible with any published hash.
+///
+/// Integer slices are not portable, see [`FxHasher`](FxHasher#portability).
+pub struct MixHasher {
+    state: u64,
+}
+
+const MIX_P0: u64 = 0xa0761d6478bd642f;
+const MIX_P1: u64 = 0xe7037ed1a0b428db;
+const MIX_P2: u64 = 0x8ebc6af09c88c6e3;
+const MIX_P3: u64 = 0x589965cc75374cc3;
+
+impl MixHasher {
+    pub const fn with_seed(seed: u64) -> Self {
+        let full = (seed ^ MIX_P0) as u128 * MIX_P1 as u128;
+        MixHasher { state: seed ^ (full as u64) ^ ((full >> 64) as u64) }
+    }
+}
+
+impl Hasher for MixHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut state = self.state;
+        for_each_pair(bytes, |a, b| state = folded_multiply(a ^ MIX_P1, b ^ state));
+        self.state = folded_multiply(state ^ MIX_P3, bytes.len() as u64 ^ MIX_P1);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.state = folded_multiply(i ^ MIX_P1, self.state ^ MIX_P2);
+    }
+
+    portable_hasher!(@write);
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        folded_multiply(self.state ^ MIX_P2, MIX_P3)
+    }
+}
+
+portable_hasher!(Acc, AccHasher);
+
+/// Portable accumulator hash
+///
+/// Each write is one folded multiply into the accumulator,
+/// bytes are read as little-endian u64 pairs with the length mixed in.
+/// Between [`FxHasher`] and [`MixHasher`] in speed and quality.
+/// It is not compatible with any published hash.
+///
+/// Integer slices are not portable, see [`FxHasher`](FxHasher#portability).
+pub struct AccHasher {
+    accumulator: u64,
+}
+
+const ACC_A1: u64 = 0x13198a2e03707344;
+const ACC_A2: u64 = 0xa4093822299f31d0;
+const ACC_A3: u64 = 0x082efa98ec4e6c89;
+const ACC_A4: u64 = 0x452821e638d01377;
+
+impl AccHasher {
+    pub const fn with_seed(seed: u64) -> Self {
+        AccHasher { accumulator: seed }
+    }
+}
+
+impl Hasher for AccHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut acc = self.accumulator;
+        for_each_pair(bytes, |a, b| acc = folded_multiply(a ^ acc, b ^ ACC_A2));
+        self.accumulator = folded_multiply(acc ^ bytes.len() as u64, ACC_A3);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.accumulator = folded_multiply(i ^ self.accumulator, ACC_A1);
+    }
+
+    portable_hasher!(@write);
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        folded_multiply(self.accumulator, ACC_A4)
+    }
+}
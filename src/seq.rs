@@ -20,16 +20,304 @@ where
     #[inline(always)]
     fn index(index: usize) -> Option<Self::Item> {
         let start: usize = match index.checked_sub(1) {
-            Some(index) => SEQ::index(index)?.try_into().unwrap(),
+            Some(index) => SEQ::index(index)?.try_into().ok()?,
             None => 0
         };
         let end: usize = SEQ::index(index)?
             .try_into()
-            .unwrap();
+            .ok()?;
         BUF::as_data().get(start..end)
     }
 }
 
+/// Position seq of utf8 strings
+///
+/// Same layout as [`PositionSeq`], each item is checked by [`core::str::from_utf8`],
+/// so it is a little slower than bytes.
+pub struct StrPositionSeq<SEQ, BUF>(PhantomData<(SEQ, BUF)>);
+
+impl<
+    const B: usize,
+    SEQ,
+    BUF,
+> AccessSeq for StrPositionSeq<SEQ, BUF>
+where
+    SEQ: AccessSeq<Item = u32>,
+    BUF: AsData<Data = [u8; B]>
+{
+    type Item = &'static str;
+
+    const LEN: usize = SEQ::LEN;
+
+    #[inline(always)]
+    fn index(index: usize) -> Option<Self::Item> {
+        let buf = PositionSeq::<SEQ, BUF>::index(index)?;
+        core::str::from_utf8(buf).ok()
+    }
+}
+
+/// Little-endian u16 seq over bytes
+pub struct U16Seq<BUF>(PhantomData<BUF>);
+
+impl<const B: usize, BUF> AccessSeq for U16Seq<BUF>
+where
+    BUF: AsData<Data = [u8; B]>
+{
+    type Item = u16;
+    const LEN: usize = {
+        if !B.is_multiple_of(2) {
+            panic!();
+        }
+
+        B / 2
+    };
+
+    #[inline(always)]
+    fn index(index: usize) -> Option<Self::Item> {
+        let buf = BUF::as_data().get(index * 2..)?.get(..2)?;
+        Some(u16::from_le_bytes([buf[0], buf[1]]))
+    }
+}
+
+/// Elias-Fano encoded monotone seq
+///
+/// `N` is the length and `L` is the number of low bits,
+/// `LO` is the packed low bits, `HI` is the bitvector of the unary-coded high bits,
+/// and `S` is the position of every 64th one in `HI`, used as select index.
+///
+/// If the ones of a block span 1024 bits of `HI` or more,
+/// its sample is the offset of the block in `E` with the highest bit set,
+/// and `E` stores the position of each one of the block.
+/// So a lookup reads at most 33 words of `HI`, regardless of the gaps.
+pub struct EliasFanoSeq<
+    const N: usize,
+    const L: usize,
+    S,
+    E,
+    LO,
+    HI
+>(PhantomData<(S, E, LO, HI)>);
+
+/// Flag of a sample of [`EliasFanoSeq`] whose block stores the positions
+pub(crate) const EF_SPARSE_BLOCK: u32 = 1 << 31;
+
+impl<
+    const N: usize,
+    const L: usize,
+    S,
+    E,
+    LO,
+    HI,
+> AccessSeq for EliasFanoSeq<N, L, S, E, LO, HI>
+where
+    S: AccessSeq<Item = u32>,
+    E: AccessSeq<Item = u32>,
+    LO: AccessSeq<Item = u32>,
+    HI: AccessSeq<Item = u32>,
+{
+    type Item = u32;
+    const LEN: usize = {
+        if L > 32 {
+            panic!();
+        }
+
+        N
+    };
+
+    #[inline]
+    fn index(index: usize) -> Option<Self::Item> {
+        if index >= Self::LEN {
+            return None;
+        }
+
+        let low = read_bits::<LO>(index.checked_mul(L)?, L)?;
+        let sample = S::index(index / 64)?;
+
+        let pos: usize = if sample & EF_SPARSE_BLOCK != 0 {
+            let offset: usize = (sample & !EF_SPARSE_BLOCK).try_into().ok()?;
+            E::index(offset + index % 64)?.try_into().ok()?
+        } else {
+            // select the `index`-th one from the nearest sample,
+            // the block spans less than `EF_MAX_SPAN` bits.
+            let start: usize = sample.try_into().ok()?;
+            let mut rank = index % 64;
+            let mut word_idx = start / 32;
+            let mut word = HI::index(word_idx)? & (u32::MAX << (start % 32));
+
+            loop {
+                let ones: usize = word.count_ones().try_into().ok()?;
+
+                if rank < ones {
+                    break
+                }
+
+                rank -= ones;
+                word_idx += 1;
+                word = HI::index(word_idx)?;
+            }
+
+            for _ in 0..rank {
+                word &= word - 1;
+            }
+
+            word_idx * 32 + usize::try_from(word.trailing_zeros()).ok()?
+        };
+
+        let high = u64::try_from(pos.checked_sub(index)?).ok()?;
+        ((high << L) | low).try_into().ok()
+    }
+}
+
+/// Bit packed seq with frame-of-reference
+///
+/// Each item is stored as `item - BASE` in `BITS` bits,
+/// `D` is the packed u32 words, `T` is the item type, `u32` or `u64`.
+pub struct PackedSeq<
+    const N: usize,
+    const BITS: usize,
+    const BASE: u64,
+    T,
+    D
+>(PhantomData<(T, D)>);
+
+impl<
+    const N: usize,
+    const BITS: usize,
+    const BASE: u64,
+    D,
+> AccessSeq for PackedSeq<N, BITS, BASE, u32, D>
+where
+    D: AccessSeq<Item = u32>
+{
+    type Item = u32;
+    const LEN: usize = {
+        if BITS > 32 {
+            panic!();
+        }
+
+        N
+    };
+
+    #[inline]
+    fn index(index: usize) -> Option<Self::Item> {
+        if index >= Self::LEN {
+            return None;
+        }
+
+        let n = read_bits::<D>(index.checked_mul(BITS)?, BITS)?;
+        n.checked_add(BASE)?.try_into().ok()
+    }
+}
+
+impl<
+    const N: usize,
+    const BITS: usize,
+    const BASE: u64,
+    D,
+> AccessSeq for PackedSeq<N, BITS, BASE, u64, D>
+where
+    D: AccessSeq<Item = u32>
+{
+    type Item = u64;
+    const LEN: usize = {
+        if BITS > 64 {
+            panic!();
+        }
+
+        N
+    };
+
+    #[inline]
+    fn index(index: usize) -> Option<Self::Item> {
+        if index >= Self::LEN {
+            return None;
+        }
+
+        let n = read_bits::<D>(index.checked_mul(BITS)?, BITS)?;
+        n.checked_add(BASE)
+    }
+}
+
+/// Escape coded u8 seq
+///
+/// Each item is a `BITS` bits code in `C`, the all-ones code is an escape
+/// and the item is in exceptions `E` instead.
+/// `R` is the number of escapes before every 64th item,
+/// so an escaped lookup reads at most 63 codes to find its exception.
+pub struct EscapedSeq<const N: usize, const BITS: usize, C, R, E>(PhantomData<(C, R, E)>);
+
+impl<
+    const N: usize,
+    const BITS: usize,
+    C,
+    R,
+    E,
+> AccessSeq for EscapedSeq<N, BITS, C, R, E>
+where
+    C: AccessSeq<Item = u32>,
+    R: AccessSeq<Item = u32>,
+    E: AccessSeq<Item = u8>,
+{
+    type Item = u8;
+    const LEN: usize = {
+        if BITS == 0 || BITS >= 8 {
+            panic!();
+        }
+
+        N
+    };
+
+    #[inline]
+    fn index(index: usize) -> Option<Self::Item> {
+        if index >= Self::LEN {
+            return None;
+        }
+
+        let escape = (1 << BITS) - 1;
+        let code = read_bits::<C>(index.checked_mul(BITS)?, BITS)?;
+
+        if code != escape {
+            return Some(code as u8);
+        }
+
+        let block = index / 64;
+        let mut rank: usize = R::index(block)?.try_into().ok()?;
+        for i in block * 64..index {
+            if read_bits::<C>(i * BITS, BITS)? == escape {
+                rank += 1;
+            }
+        }
+
+        E::index(rank)
+    }
+}
+
+/// Read `bits` bits from the packed u32 words at bit position `pos`.
+#[inline(always)]
+fn read_bits<SEQ: AccessSeq<Item = u32>>(pos: usize, bits: usize) -> Option<u64> {
+    if bits == 0 {
+        return Some(0);
+    }
+
+    let word_idx = pos / 32;
+    let offset = pos % 32;
+    let end = offset + bits;
+
+    let mut v = u64::from(SEQ::index(word_idx)?) >> offset;
+    if end > 32 {
+        v |= u64::from(SEQ::index(word_idx + 1)?) << (32 - offset);
+    }
+    if end > 64 {
+        v |= u64::from(SEQ::index(word_idx + 2)?) << (64 - offset);
+    }
+
+    if bits < 64 {
+        v &= (1 << bits) - 1;
+    }
+
+    Some(v)
+}
+
 pub struct PooledSeq<SEQ, ID>(PhantomData<(SEQ, ID)>);
 
 pub trait PooledId: From<u32> + Copy {
@@ -52,11 +340,11 @@ where
 }
 
 #[inline(always)]
-pub fn pooled_unpack(n: u32) -> (usize, usize) {
+pub fn pooled_unpack(n: u32) -> Option<(usize, usize)> {
     const BIT: usize = 24;
 
-    let offset = (n & ((1 << BIT) - 1)).try_into().unwrap();
-    let len = (n >> BIT).try_into().unwrap();
-    
-    (offset, len)
+    let offset = (n & ((1 << BIT) - 1)).try_into().ok()?;
+    let len = (n >> BIT).try_into().ok()?;
+
+    Some((offset, len))
 }
@@ -0,0 +1,187 @@
+//! Stable key hashing
+//!
+//! [`Hash`] writes `usize` lengths and native-endian integers,
+//! so the same key may hash differently on the build host and the target.
+//! [`StableHash`] writes a canonical encoding instead:
+//!
+//! * integers are little-endian bytes of their width, `usize` and `isize` are 64-bit.
+//! * `bool` is `u8`, `char` is `u32`.
+//! * slices are 64-bit length followed by items, `str` is same as `[u8]`.
+//! * arrays and tuples are items in order, without length.
+//!
+//! All bytes are written by [`Hasher::write`],
+//! so the hasher must be platform-independent for bytes,
+//! such as [`Mix`](crate::phf::Mix) and the others in [`phf`](crate::phf).
+//! This also makes integer slices portable, which those hashers are not by [`Hash`].
+//!
+//! Use [`Stable`] as the hash parameter of map,
+//! and [`Stable::hash_one`] or [`MapBuilder::set_stable_hash`](crate::builder::MapBuilder::set_stable_hash)
+//! in builder, so that both sides agree.
+
+use core::hash::{ Hash, Hasher };
+use core::marker::PhantomData;
+use crate::phf::HashOne;
+use crate::equivalent::Hashable;
+
+/// Hash with canonical encoding
+pub trait StableHash {
+    fn stable_hash<S: Hasher>(&self, state: &mut S);
+
+    /// Writes the items of slice, without length.
+    fn stable_hash_slice<S: Hasher>(data: &[Self], state: &mut S)
+    where
+        Self: Sized
+    {
+        for item in data {
+            item.stable_hash(state);
+        }
+    }
+}
+
+/// Hash parameter of map for [`StableHash`] keys
+pub struct Stable<H>(PhantomData<H>);
+
+impl<H: HashOne> Stable<H> {
+    #[inline]
+    pub fn hash_one<T: StableHash + ?Sized>(k: u64, v: &T) -> u64 {
+        H::hash_one(k, AsHash(v))
+    }
+}
+
+impl<T: StableHash + ?Sized, H: HashOne> Hashable<Stable<H>> for T {
+    #[inline]
+    fn hash(&self, seed: u64) -> u64 {
+        Stable::<H>::hash_one(seed, self)
+    }
+}
+
+struct AsHash<'a, T: ?Sized>(&'a T);
+
+impl<T: StableHash + ?Sized> Hash for AsHash<'_, T> {
+    #[inline]
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        self.0.stable_hash(state);
+    }
+}
+
+macro_rules! int {
+    ( $( $ty:ty ),* ) => {
+        $(
+            impl StableHash for $ty {
+                #[inline]
+                fn stable_hash<S: Hasher>(&self, state: &mut S) {
+                    state.write(&self.to_le_bytes());
+                }
+            }
+        )*
+    }
+}
+
+int!(i8, u16, i16, u32, i32, u64, i64, u128, i128);
+
+impl StableHash for u8 {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        state.write(&[*self]);
+    }
+
+    #[inline]
+    fn stable_hash_slice<S: Hasher>(data: &[Self], state: &mut S) {
+        state.write(data);
+    }
+}
+
+impl StableHash for usize {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        (*self as u64).stable_hash(state);
+    }
+}
+
+impl StableHash for isize {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        (*self as i64).stable_hash(state);
+    }
+}
+
+impl StableHash for bool {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        u8::from(*self).stable_hash(state);
+    }
+}
+
+impl StableHash for char {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        u32::from(*self).stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.len().stable_hash(state);
+        T::stable_hash_slice(self, state);
+    }
+}
+
+impl StableHash for str {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.as_bytes().stable_hash(state);
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        T::stable_hash_slice(self, state);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        (**self).stable_hash(state);
+    }
+}
+
+macro_rules! tuple {
+    ( $( $name:ident )+ ) => {
+        impl<$( $name: StableHash ),+> StableHash for ($( $name, )+) {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn stable_hash<S: Hasher>(&self, state: &mut S) {
+                let ($( $name, )+) = self;
+                $( $name.stable_hash(state); )+
+            }
+        }
+    }
+}
+
+tuple!(A);
+tuple!(A B);
+tuple!(A B C);
+tuple!(A B C D);
+tuple!(A B C D E);
+tuple!(A B C D E F);
+
+// The owned types for builder keys, the runtime is `no_std`.
+
+#[cfg(feature = "builder")]
+impl StableHash for String {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.as_str().stable_hash(state);
+    }
+}
+
+#[cfg(feature = "builder")]
+impl<T: StableHash> StableHash for Vec<T> {
+    #[inline]
+    fn stable_hash<S: Hasher>(&self, state: &mut S) {
+        self.as_slice().stable_hash(state);
+    }
+}
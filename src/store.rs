@@ -1,5 +1,7 @@
+use core::cmp::Ordering;
 use core::marker::PhantomData;
 use crate::equivalent::Comparable;
+use crate::eytzinger;
 
 pub trait AsData {
     type Data: ?Sized;
@@ -18,6 +20,45 @@ pub trait Searchable: MapStore {
     fn search<Q>(query: &Q) -> Option<usize>
     where
         Q: Comparable<Self::Key> + ?Sized;
+
+    /// Returns the insertion point of query,
+    /// that is the index of first key that is not less than query.
+    fn lower_bound<Q>(query: &Q) -> usize
+    where
+        Q: Comparable<Self::Key> + ?Sized
+    {
+        partition_point(Self::LEN, |idx| Self::get_key(idx)
+            .is_some_and(|key| query.compare(&key) == Ordering::Greater)
+        )
+    }
+
+    /// Returns the index of first key that is greater than query.
+    fn upper_bound<Q>(query: &Q) -> usize
+    where
+        Q: Comparable<Self::Key> + ?Sized
+    {
+        partition_point(Self::LEN, |idx| Self::get_key(idx)
+            .is_some_and(|key| query.compare(&key) != Ordering::Less)
+        )
+    }
+}
+
+#[doc(hidden)]
+pub fn partition_point(len: usize, mut pred: impl FnMut(usize) -> bool) -> usize {
+    let mut lo = 0;
+    let mut hi = len;
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    lo
 }
 
 pub trait MapStore {
@@ -28,6 +69,88 @@ pub trait MapStore {
 
     fn get_key(index: usize) -> Option<Self::Key>;
     fn get_value(index: usize) -> Option<Self::Value>;    
+
+    /// Checks the fingerprint of slot before loading the key,
+    /// returns `false` if the key must not be in this slot.
+    #[inline(always)]
+    fn check_fingerprint(_index: usize, _hash: u64) -> bool {
+        true
+    }
+}
+
+/// Keyless store
+///
+/// It does not store any key, so lookups can only be made with `index_of`,
+/// which doesn't verify the key.
+pub struct Keyless<const N: usize>;
+
+impl<const N: usize> AccessSeq for Keyless<N> {
+    type Item = ();
+    const LEN: usize = N;
+
+    #[inline(always)]
+    fn index(index: usize) -> Option<Self::Item> {
+        (index < N).then_some(())
+    }
+}
+
+/// Fingerprint derived from hash
+pub trait Fingerprint: Copy + PartialEq {
+    fn from_hash(hash: u64) -> Self;
+}
+
+impl Fingerprint for u8 {
+    #[inline(always)]
+    fn from_hash(hash: u64) -> Self {
+        // Bucket and slot use the high bits of `low` and `high`,
+        // so the low bits are left for fingerprint.
+        (crate::low(hash) ^ crate::high(hash)) as u8
+    }
+}
+
+impl Fingerprint for u16 {
+    #[inline(always)]
+    fn from_hash(hash: u64) -> Self {
+        (crate::low(hash) ^ crate::high(hash)) as u16
+    }
+}
+
+/// Map store with a fingerprint column
+///
+/// `F` is the fingerprint of each slot, in the same order as `D`.
+pub struct WithFingerprint<F, D>(PhantomData<(F, D)>);
+
+impl<F, D> MapStore for WithFingerprint<F, D>
+where
+    F: AccessSeq,
+    F::Item: Fingerprint,
+    D: MapStore
+{
+    type Key = D::Key;
+    type Value = D::Value;
+
+    const LEN: usize = {
+        if F::LEN != D::LEN {
+            panic!();
+        }
+
+        D::LEN
+    };
+
+    #[inline(always)]
+    fn get_key(index: usize) -> Option<Self::Key> {
+        D::get_key(index)
+    }
+
+    #[inline(always)]
+    fn get_value(index: usize) -> Option<Self::Value> {
+        D::get_value(index)
+    }
+
+    #[inline(always)]
+    fn check_fingerprint(index: usize, hash: u64) -> bool {
+        F::index(index) == Some(F::Item::from_hash(hash))
+    }
 }
 
 pub struct SliceData<
@@ -124,16 +247,35 @@ where
     {
         K::search(query)
     }
+
+    fn lower_bound<Q>(query: &Q) -> usize
+    where
+        Q: Comparable<K::Item> + ?Sized
+    {
+        K::lower_bound(query)
+    }
+
+    fn upper_bound<Q>(query: &Q) -> usize
+    where
+        Q: Comparable<K::Item> + ?Sized
+    {
+        K::upper_bound(query)
+    }
 }
 
 pub struct MapIter<'iter, D> {
     next: usize,
+    end: usize,
     _phantom: PhantomData<&'iter D>
 }
 
-impl<'iter, D> MapIter<'iter, D> {
+impl<'iter, D: MapStore> MapIter<'iter, D> {
     pub(super) const fn new() -> Self {
-        MapIter { next: 0, _phantom: PhantomData }
+        MapIter::range(0, D::LEN)
+    }
+
+    pub(super) const fn range(start: usize, end: usize) -> Self {
+        MapIter { next: start, end, _phantom: PhantomData }
     }
 }
 
@@ -144,7 +286,7 @@ where
     type Item = (D::Key, D::Value);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next < D::LEN {
+        if self.next < self.end {
             let k = D::get_key(self.next)?;
             let v = D::get_value(self.next)?;
             self.next += 1;
@@ -153,6 +295,27 @@ where
             None
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<'iter, D> DoubleEndedIterator for MapIter<'iter, D>
+where
+    D: MapStore
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.next < self.end {
+            let k = D::get_key(self.end - 1)?;
+            let v = D::get_value(self.end - 1)?;
+            self.end -= 1;
+            Some((k, v))
+        } else {
+            None
+        }
+    }
 }
 
 impl<'iter, D> ExactSizeIterator for MapIter<'iter, D>
@@ -160,7 +323,7 @@ where
     D: MapStore
 {
     fn len(&self) -> usize {
-        D::LEN
+        self.end - self.next
     }
 }
 
@@ -169,6 +332,142 @@ impl<'iter, D> Clone for MapIter<'iter, D> {
     fn clone(&self) -> Self {
         MapIter {
             next: self.next,
+            end: self.end,
+            _phantom: PhantomData
+        }
+    }
+}
+
+pub struct SetIter<'iter, K> {
+    next: usize,
+    _phantom: PhantomData<&'iter K>
+}
+
+impl<'iter, K> SetIter<'iter, K> {
+    pub(super) const fn new() -> Self {
+        SetIter { next: 0, _phantom: PhantomData }
+    }
+}
+
+impl<'iter, K> Iterator for SetIter<'iter, K>
+where
+    K: MapStore
+{
+    type Item = K::Key;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next < K::LEN {
+            let k = K::get_key(self.next)?;
+            self.next += 1;
+            Some(k)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'iter, K> ExactSizeIterator for SetIter<'iter, K>
+where
+    K: MapStore
+{
+    fn len(&self) -> usize {
+        K::LEN - self.next
+    }
+}
+
+impl<'iter, K> Clone for SetIter<'iter, K> {
+    #[inline]
+    fn clone(&self) -> Self {
+        SetIter {
+            next: self.next,
+            _phantom: PhantomData
+        }
+    }
+}
+
+/// Iterator of ordered map in ascending order
+pub struct OrderedIter<'iter, D> {
+    front: usize,
+    back: usize,
+    len: usize,
+    _phantom: PhantomData<&'iter D>
+}
+
+impl<'iter, D: MapStore> OrderedIter<'iter, D> {
+    /// `start` and `end` are the nodes of range, `0` means the end.
+    pub(super) fn range(start: usize, end: usize) -> Self {
+        let len = eytzinger::rank(end, D::LEN)
+            .saturating_sub(eytzinger::rank(start, D::LEN));
+        let back = match end {
+            0 => eytzinger::last(D::LEN),
+            end => eytzinger::prev(end, D::LEN)
+        };
+
+        OrderedIter {
+            front: start,
+            back,
+            len,
+            _phantom: PhantomData
+        }
+    }
+}
+
+impl<'iter, D> Iterator for OrderedIter<'iter, D>
+where
+    D: MapStore
+{
+    type Item = (D::Key, D::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            let k = D::get_key(self.front - 1)?;
+            let v = D::get_value(self.front - 1)?;
+            self.front = eytzinger::next(self.front, D::LEN);
+            self.len -= 1;
+            Some((k, v))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'iter, D> DoubleEndedIterator for OrderedIter<'iter, D>
+where
+    D: MapStore
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            let k = D::get_key(self.back - 1)?;
+            let v = D::get_value(self.back - 1)?;
+            self.back = eytzinger::prev(self.back, D::LEN);
+            self.len -= 1;
+            Some((k, v))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'iter, D> ExactSizeIterator for OrderedIter<'iter, D>
+where
+    D: MapStore
+{
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'iter, D> Clone for OrderedIter<'iter, D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        OrderedIter {
+            front: self.front,
+            back: self.back,
+            len: self.len,
             _phantom: PhantomData
         }
     }